@@ -3,12 +3,48 @@ use bevy::prelude::*;
 // Tetris game constants
 pub const BOARD_COLS: usize = 10;
 pub const BOARD_ROWS: usize = 20;
-pub const BOARD_CENTER_X: f32 = BOARD_COLS as f32 / 2.0;
-pub const BOARD_CENTER_Y: f32 = BOARD_ROWS as f32 / 2.0;
+/// Hidden rows above the cup, the pieces spawn in the first two of them and move down one row
+pub const BOARD_BUFFER_ROWS: usize = 20;
+/// The smallest and the biggest board sizes that can be chosen
+pub const BOARD_MIN_SIZE: usize = 4;
+pub const BOARD_MAX_SIZE: usize = 40;
 pub const BLOCK_SIZE: f32 = 30.0;
 pub const BLOCK_SPRITE_SIZE: f32 = 28.0;
 pub const BORDER_SIZE: f32 = 5.0;
 pub const BORDER_COLOR: Color = Color::WHITE;
 pub const CACHED_PIECES: usize = 7;
-/// The number of rows that are visible to the top of the cup
-pub const VISIBILITY_LIMIT_Y: i32 = 21;
\ No newline at end of file
+/// How many upcoming pieces are shown by default
+pub const NEXT_PREVIEW: usize = 5;
+/// The most upcoming pieces that can be shown, it can't be more than the cached pieces
+pub const NEXT_PREVIEW_MAX: usize = 6;
+/// Seconds a piece can rest on the stack before it locks
+pub const LOCK_DELAY: f32 = 0.5;
+/// How many moves or rotations can restart the lock delay of a piece
+pub const LOCK_DELAY_RESETS: u32 = 15;
+/// Seconds a horizontal key must be held before the movement repeats (DAS)
+pub const AUTO_SHIFT_DELAY: f32 = 0.167;
+/// Seconds between each repeated horizontal movement (ARR)
+pub const AUTO_REPEAT_RATE: f32 = 0.033;
+/// How many times faster than gravity the piece falls while the down key is held
+pub const SOFT_DROP_FACTOR: f32 = 20.0;
+/// How many lines the player clears to go up a level
+pub const LINES_PER_LEVEL: u64 = 10;
+/// The last level of the marathon mode, the game is complete when it is cleared
+pub const MARATHON_LEVEL: u64 = 15;
+/// The lines to clear in the sprint mode
+pub const SPRINT_LINES: u64 = 40;
+/// The default duration of the ultra mode, in seconds
+pub const ULTRA_SECONDS: u64 = 120;
+/// The default garbage rows to clear in the dig mode
+pub const DIG_ROWS: usize = 10;
+/// The default chance the hole of a garbage row moves to another column, 1 moves it every row
+pub const GARBAGE_HOLE_CHANGE: f64 = 1.0;
+/// Seconds before the first garbage row rises in the survival mode
+pub const SURVIVAL_RISE_SECONDS: f32 = 8.0;
+/// The garbage never rises faster than one row every this many seconds
+pub const SURVIVAL_MIN_RISE_SECONDS: f32 = 1.0;
+/// Each garbage row rises after this fraction of the time the last one took
+pub const SURVIVAL_RISE_SPEED_UP: f32 = 0.95;
+pub const GARBAGE_COLOR: Color = Color::srgb(0.5, 0.5, 0.5);
+/// How many scores are kept in the leaderboard of each mode
+pub const LEADERBOARD_SIZE: usize = 5;
@@ -1,22 +1,55 @@
 use bevy::prelude::*;
 
-use crate::common::{BLOCK_SIZE, BOARD_COLS, BOARD_ROWS, BORDER_COLOR, BORDER_SIZE};
+use crate::common::{
+    BLOCK_SIZE, BOARD_BUFFER_ROWS, BOARD_COLS, BOARD_MAX_SIZE, BOARD_MIN_SIZE, BOARD_ROWS,
+    BORDER_COLOR, BORDER_SIZE,
+};
 
-pub fn setup(mut commands: Commands) {
-    let half_x = BOARD_COLS as f32 / 2.0;
-    let half_y = BOARD_ROWS as f32 / 2.0;
+/// The size of the cup, chosen when the game starts
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoardSize {
+    pub cols: usize,
+    pub rows: usize,
+}
+
+impl BoardSize {
+    pub fn new(cols: usize, rows: usize) -> Self {
+        Self {
+            cols: cols.clamp(BOARD_MIN_SIZE, BOARD_MAX_SIZE),
+            rows: rows.clamp(BOARD_MIN_SIZE, BOARD_MAX_SIZE),
+        }
+    }
+
+    /// The center of the cup in the world, the blocks start at the bottom left corner
+    pub fn center(&self) -> Vec2 {
+        Vec2::new(self.cols as f32, self.rows as f32) * BLOCK_SIZE / 2.0
+    }
+
+    /// The size of the cup in the world, with the borders
+    pub fn world_size(&self) -> Vec2 {
+        Vec2::new(self.cols as f32, self.rows as f32) * BLOCK_SIZE + 2.0 * BORDER_SIZE
+    }
+}
+
+impl Default for BoardSize {
+    fn default() -> Self {
+        Self::new(BOARD_COLS, BOARD_ROWS)
+    }
+}
+
+pub fn setup(mut commands: Commands, size: Res<BoardSize>) {
+    let center = size.center();
+    let world_size = size.world_size();
     // Lots of trial and error to get the cup to look right
-    let side = half_x * BLOCK_SIZE + BORDER_SIZE;
-    let bottom = half_y * BLOCK_SIZE + BORDER_SIZE;
-    let size_y = BOARD_ROWS as f32 * BLOCK_SIZE + 2.0 * BORDER_SIZE;
-    let size_x = BOARD_COLS as f32 * BLOCK_SIZE + 2.0 * BORDER_SIZE;
+    let side = center.x + BORDER_SIZE;
+    let bottom = center.y + BORDER_SIZE;
     // Draw the cup
 
     // Draw the left
     commands.spawn(SpriteBundle {
-        transform: Transform::from_xyz(-side, 0.0, 0.0).with_scale(Vec3::new(
+        transform: Transform::from_xyz(center.x - side, center.y, 0.0).with_scale(Vec3::new(
             BORDER_SIZE,
-            size_y,
+            world_size.y,
             0.0,
         )),
         sprite: Sprite {
@@ -27,9 +60,9 @@ pub fn setup(mut commands: Commands) {
     });
     // Draw the right
     commands.spawn(SpriteBundle {
-        transform: Transform::from_xyz(side, 0.0, 0.0).with_scale(Vec3::new(
+        transform: Transform::from_xyz(center.x + side, center.y, 0.0).with_scale(Vec3::new(
             BORDER_SIZE,
-            size_y,
+            world_size.y,
             0.0,
         )),
         sprite: Sprite {
@@ -38,10 +71,22 @@ pub fn setup(mut commands: Commands) {
         },
         ..default()
     });
+    // Hide the blocks above the cup, so they are clipped at the top of it.
+    // It is drawn in front of the blocks with the color of the background.
+    let buffer_height = BOARD_BUFFER_ROWS as f32 * BLOCK_SIZE;
+    commands.spawn(SpriteBundle {
+        transform: Transform::from_xyz(center.x, center.y * 2.0 + buffer_height / 2.0, 10.0)
+            .with_scale(Vec3::new(size.cols as f32 * BLOCK_SIZE, buffer_height, 0.0)),
+        sprite: Sprite {
+            color: Color::BLACK,
+            ..default()
+        },
+        ..default()
+    });
     // Draw the bottom
     commands.spawn(SpriteBundle {
-        transform: Transform::from_xyz(0.0, -bottom, 0.0).with_scale(Vec3::new(
-            size_x,
+        transform: Transform::from_xyz(center.x, center.y - bottom, 0.0).with_scale(Vec3::new(
+            world_size.x,
             BORDER_SIZE,
             0.0,
         )),
@@ -1,17 +1,22 @@
 mod common;
 mod grid;
+mod mode;
 mod piece;
 mod state;
 mod stats;
 mod ui;
 
-use bevy::prelude::*;
+use bevy::{prelude::*, window::PrimaryWindow};
 #[cfg(debug_assertions)]
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 
-use piece::TetrisPiecePlugin;
+use std::{str::FromStr, time::Duration};
+
+use grid::BoardSize;
+use mode::{GameModePlugin, ModeSettings};
+use piece::{GravityCurve, Handling, Rules, Seed, TetrisPiecePlugin};
 use state::{AppState, GameState};
-use stats::StatsPlugin;
+use stats::{Level, NextPreview, StatsPlugin};
 use ui::TetrisUIPlugin;
 
 /// This is our entry point for the game
@@ -22,16 +27,78 @@ fn main() {
     #[cfg(debug_assertions)]
     app.add_plugins(WorldInspectorPlugin::default());
 
+    let gravity = arg_value::<GravityCurve>("--gravity");
     app.insert_resource(ClearColor(Color::BLACK))
+        .insert_resource(Seed::new(arg_value("--seed")))
+        .insert_resource(BoardSize::new(
+            arg_value("--cols").unwrap_or(common::BOARD_COLS),
+            arg_value("--rows").unwrap_or(common::BOARD_ROWS),
+        ))
+        .insert_resource(ModeSettings {
+            ultra_time: Duration::from_secs(
+                arg_value("--ultra-time").unwrap_or(common::ULTRA_SECONDS),
+            ),
+            zen_gravity: gravity.is_some(),
+            dig_rows: arg_value("--dig-rows").unwrap_or(common::DIG_ROWS),
+            ..default()
+        })
+        .insert_resource(Rules {
+            partial_lock_out: arg_flag("--partial-lock-out"),
+            hole_change: arg_value("--hole-change").unwrap_or(common::GARBAGE_HOLE_CHANGE),
+            ..default()
+        })
+        .insert_resource(gravity.unwrap_or_default())
+        .insert_resource(Level::new(arg_value("--level").unwrap_or(1)))
+        .insert_resource(Handling::new(
+            arg_value("--das").unwrap_or(common::AUTO_SHIFT_DELAY),
+            arg_value("--arr").unwrap_or(common::AUTO_REPEAT_RATE),
+            arg_value("--soft-drop").unwrap_or(common::SOFT_DROP_FACTOR),
+        ))
+        .insert_resource(NextPreview::new(
+            arg_value("--preview").unwrap_or(common::NEXT_PREVIEW),
+        ))
         .init_state::<AppState>()
         .add_sub_state::<GameState>()
         .enable_state_scoped_entities::<AppState>()
         .enable_state_scoped_entities::<GameState>()
-        .add_plugins((TetrisUIPlugin, TetrisPiecePlugin, StatsPlugin))
+        .add_plugins((
+            TetrisUIPlugin,
+            TetrisPiecePlugin,
+            StatsPlugin,
+            GameModePlugin,
+        ))
         .add_systems(Startup, (setup_camera, grid::setup))
         .run();
 }
 
-fn setup_camera(mut commands: Commands) {
-    commands.spawn(Camera2dBundle::default());
+/// Read an option from the command line, given as `<name> <value>`
+fn arg_value<T: FromStr>(name: &str) -> Option<T> {
+    let mut args = std::env::args();
+    args.find(|arg| arg == name)?;
+    args.next()?.parse().ok()
+}
+
+/// Check if a flag is given in the command line
+fn arg_flag(name: &str) -> bool {
+    std::env::args().any(|arg| arg == name)
+}
+
+/// Place the camera over the cup, zooming out if the cup doesn't fit between the side panels
+fn setup_camera(
+    mut commands: Commands,
+    size: Res<BoardSize>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+) {
+    let mut camera = Camera2dBundle::default();
+    let center = size.center();
+    camera.transform.translation.x = center.x;
+    camera.transform.translation.y = center.y;
+    if let Ok(window) = q_window.get_single() {
+        let world_size = size.world_size();
+        // The side panels take 35% of the width each
+        camera.projection.scale = (world_size.x / (window.width() * 0.3))
+            .max(world_size.y / (window.height() * 0.95))
+            .max(1.0);
+    }
+    commands.spawn(camera);
 }
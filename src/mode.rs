@@ -0,0 +1,302 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{
+    common::{DIG_ROWS, MARATHON_LEVEL, SPRINT_LINES, ULTRA_SECONDS},
+    piece::{GameOverEvent, Playfield, RandomizerKind, Rules},
+    state::GameState,
+    stats::{BestTimes, Leaderboards, Level, Score, Session, StatsSet},
+};
+
+/// The game modes the player can choose in the main menu
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameMode {
+    /// Play until the stack reaches the top, the original game and the endless variant of
+    /// the marathon
+    #[default]
+    Endless,
+    /// Clear the levels up to level 15
+    Marathon,
+    /// Clear 40 lines as fast as possible
+    Sprint,
+    /// Score as much as possible before the time runs out
+    Ultra,
+    /// Practice without gravity, topping out only clears the bottom rows
+    Zen,
+    /// Clear the garbage rows the board starts with as fast as possible
+    Dig,
+    /// Survive as long as possible while the garbage rises from the bottom
+    Survival,
+}
+
+/// The settings of the modes that can be changed
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct ModeSettings {
+    /// The duration of the ultra mode
+    pub ultra_time: Duration,
+    /// The player chose a gravity curve, so the zen mode uses it
+    pub zen_gravity: bool,
+    /// The garbage rows of the dig mode
+    pub dig_rows: usize,
+    /// The randomizer chosen in the menu, used instead of the one of the mode
+    pub randomizer: Option<RandomizerKind>,
+}
+
+impl ModeSettings {
+    /// Choose the next randomizer in the menu, going back to the one of the mode after the last
+    pub fn cycle_randomizer(&mut self) {
+        self.randomizer = match self.randomizer {
+            None => Some(RandomizerKind::default()),
+            Some(RandomizerKind::Tgm) => None,
+            Some(kind) => Some(kind.cycle()),
+        };
+    }
+}
+
+impl Default for ModeSettings {
+    fn default() -> Self {
+        Self {
+            ultra_time: Duration::from_secs(ULTRA_SECONDS),
+            zen_gravity: false,
+            dig_rows: DIG_ROWS,
+            randomizer: None,
+        }
+    }
+}
+
+impl GameMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Endless => "ENDLESS",
+            GameMode::Marathon => "MARATHON",
+            GameMode::Sprint => "SPRINT",
+            GameMode::Ultra => "ULTRA",
+            GameMode::Zen => "ZEN",
+            GameMode::Dig => "DIG",
+            GameMode::Survival => "SURVIVAL",
+        }
+    }
+
+    /// The mode after this one, to cycle through them in the menu
+    pub fn cycle(&self) -> Self {
+        match self {
+            GameMode::Endless => GameMode::Marathon,
+            GameMode::Marathon => GameMode::Sprint,
+            GameMode::Sprint => GameMode::Ultra,
+            GameMode::Ultra => GameMode::Zen,
+            GameMode::Zen => GameMode::Dig,
+            GameMode::Dig => GameMode::Survival,
+            GameMode::Survival => GameMode::Endless,
+        }
+    }
+
+    /// The randomizer of the mode, unless the player chooses another one.
+    ///
+    /// All the modes follow the guideline, so they use the 7-bag.
+    pub fn randomizer(&self) -> RandomizerKind {
+        match self {
+            GameMode::Endless
+            | GameMode::Marathon
+            | GameMode::Sprint
+            | GameMode::Ultra
+            | GameMode::Zen
+            | GameMode::Dig
+            | GameMode::Survival => RandomizerKind::SevenBag,
+        }
+    }
+
+    /// The lines to clear to finish the game, if the mode has a goal
+    pub fn line_goal(&self) -> Option<u64> {
+        match self {
+            GameMode::Sprint => Some(SPRINT_LINES),
+            _ => None,
+        }
+    }
+
+    /// The last level of the game, if the mode ends with it
+    pub fn last_level(&self) -> Option<u64> {
+        match self {
+            GameMode::Marathon => Some(MARATHON_LEVEL),
+            _ => None,
+        }
+    }
+
+    /// Check if the last level of the mode is cleared.
+    ///
+    /// A game that starts above the last level still has to clear the level it started at.
+    pub fn levels_complete(&self, level: &Level) -> bool {
+        self.last_level()
+            .is_some_and(|last| level.value > last.max(level.start))
+    }
+
+    /// The modes where the score counts for the high score
+    pub fn keeps_high_score(&self) -> bool {
+        matches!(self, GameMode::Endless | GameMode::Marathon)
+    }
+
+    /// The modes about reaching the goal as fast as possible, they keep the best time
+    pub fn is_race(&self) -> bool {
+        matches!(self, GameMode::Sprint | GameMode::Dig)
+    }
+
+    /// The timed modes keep a leaderboard of scores instead of the high score
+    pub fn has_leaderboard(&self) -> bool {
+        matches!(self, GameMode::Ultra)
+    }
+
+    /// Topping out ends the game, in every mode but zen
+    pub fn tops_out(&self) -> bool {
+        !matches!(self, GameMode::Zen)
+    }
+
+    /// The pieces fall on their own, in zen only if the player chose a gravity curve
+    pub fn has_gravity(&self, settings: &ModeSettings) -> bool {
+        !matches!(self, GameMode::Zen) || settings.zen_gravity
+    }
+
+    /// The garbage rows the board starts with, the goal of the dig mode is to clear them
+    pub fn garbage_rows(&self, settings: &ModeSettings) -> usize {
+        match self {
+            GameMode::Dig => settings.dig_rows.max(1),
+            _ => 0,
+        }
+    }
+
+    /// Garbage rows push up from the bottom during the game
+    pub fn has_rising_garbage(&self) -> bool {
+        matches!(self, GameMode::Survival)
+    }
+
+    /// The time the game lasts, if the mode is timed
+    pub fn time_limit(&self, settings: &ModeSettings) -> Option<Duration> {
+        match self {
+            GameMode::Ultra => Some(settings.ultra_time),
+            _ => None,
+        }
+    }
+}
+
+pub struct GameModePlugin;
+
+impl Plugin for GameModePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameMode>()
+            .init_resource::<ModeSettings>()
+            .add_systems(
+                Update,
+                apply_rules
+                    .run_if(resource_changed::<GameMode>.or_else(resource_changed::<ModeSettings>)),
+            )
+            .add_systems(
+                Update,
+                (
+                    check_goal.run_if(resource_changed::<Score>),
+                    check_garbage.run_if(resource_changed::<Playfield>),
+                    check_time_limit,
+                    submit_top_out.run_if(on_event::<GameOverEvent>()),
+                )
+                    .after(StatsSet)
+                    .run_if(in_state(GameState::Play)),
+            );
+    }
+}
+
+/// System to change the rules and the randomizer of the game to the ones of the chosen mode
+fn apply_rules(
+    mode: Res<GameMode>,
+    settings: Res<ModeSettings>,
+    mut rules: ResMut<Rules>,
+    mut randomizer: ResMut<RandomizerKind>,
+) {
+    *randomizer = settings.randomizer.unwrap_or(mode.randomizer());
+    rules.top_out = mode.tops_out();
+    rules.gravity = mode.has_gravity(&settings);
+    rules.garbage_rows = mode.garbage_rows(&settings);
+    rules.rising_garbage = mode.has_rising_garbage();
+}
+
+/// System to finish the game when the goal of the mode is reached
+fn check_goal(
+    mode: Res<GameMode>,
+    score: Res<Score>,
+    level: Res<Level>,
+    session: Res<Session>,
+    mut best_times: ResMut<BestTimes>,
+    mut state: ResMut<NextState<GameState>>,
+) {
+    let lines_cleared = mode.line_goal().is_some_and(|goal| score.lines >= goal);
+    if lines_cleared || mode.levels_complete(&level) {
+        best_times.record(*mode, session.time.elapsed());
+        state.set(GameState::Results);
+    }
+}
+
+/// System to finish the game when all the garbage is cleared
+fn check_garbage(
+    mode: Res<GameMode>,
+    settings: Res<ModeSettings>,
+    playfield: Res<Playfield>,
+    session: Res<Session>,
+    mut best_times: ResMut<BestTimes>,
+    mut state: ResMut<NextState<GameState>>,
+) {
+    if mode.garbage_rows(&settings) > 0 && playfield.0.garbage_rows() == 0 {
+        best_times.record(*mode, session.time.elapsed());
+        state.set(GameState::Results);
+    }
+}
+
+/// System to finish the timed modes when the time runs out
+fn check_time_limit(
+    mode: Res<GameMode>,
+    settings: Res<ModeSettings>,
+    score: Res<Score>,
+    mut session: ResMut<Session>,
+    mut leaderboards: ResMut<Leaderboards>,
+    mut state: ResMut<NextState<GameState>>,
+) {
+    let Some(limit) = mode.time_limit(&settings) else {
+        return;
+    };
+    if session.time.elapsed() >= limit {
+        session.rank = leaderboards.submit(*mode, score.value);
+        state.set(GameState::Results);
+    }
+}
+
+/// System to keep the score of a timed mode that ended with a top out before the time ran out
+fn submit_top_out(
+    mode: Res<GameMode>,
+    score: Res<Score>,
+    mut session: ResMut<Session>,
+    mut leaderboards: ResMut<Leaderboards>,
+) {
+    if mode.has_leaderboard() {
+        session.rank = leaderboards.submit(*mode, score.value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marathon_ends_after_the_last_level() {
+        for start in [1, 5, 15] {
+            let mut level = Level::new(start);
+            let lines_left = (MARATHON_LEVEL + 1 - start) * 10;
+            level.advance(lines_left - 1);
+            assert!(!GameMode::Marathon.levels_complete(&level));
+            level.advance(lines_left);
+            assert!(GameMode::Marathon.levels_complete(&level));
+            assert!(!GameMode::Endless.levels_complete(&level));
+        }
+
+        // Starting above the last level, the game ends after one level
+        let mut level = Level::new(20);
+        assert!(!GameMode::Marathon.levels_complete(&level));
+        level.advance(10);
+        assert!(GameMode::Marathon.levels_complete(&level));
+    }
+}
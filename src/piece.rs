@@ -1,13 +1,24 @@
+mod board;
 mod components;
+mod events;
+mod garbage;
+mod gravity;
+mod randomizer;
 mod resources;
 mod systems;
 
-use bevy::prelude::*;
+use bevy::{input::common_conditions::input_just_pressed, prelude::*};
 
 use crate::state::{AppState, GameState};
 
+use components::GhostBlock;
+
+pub use board::TSpin;
 pub use components::{select_piece, Block, PieceType};
-pub use resources::MoveDownTimer;
+pub use events::{GameOverEvent, PieceLockedEvent};
+pub use gravity::GravityCurve;
+pub use randomizer::RandomizerKind;
+pub use resources::{Handling, HoldPiece, MoveDownTimer, Playfield, Rules, Seed, Streak};
 
 #[derive(SystemSet, Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum TetrisSet {
@@ -17,8 +28,6 @@ pub enum TetrisSet {
     Movement,
     // The piece is checked for collisions, removed lines, and game over
     Collision,
-    // The piece visibility is controlled to avoid rendering it when it is out of the screen
-    Visibility,
 }
 
 pub struct TetrisPiecePlugin;
@@ -27,26 +36,33 @@ impl Plugin for TetrisPiecePlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<Block>()
             .register_type::<PieceType>()
+            .register_type::<GhostBlock>()
+            .init_resource::<RandomizerKind>()
+            .init_resource::<Rules>()
+            .init_resource::<Handling>()
+            .add_event::<events::PieceLockedEvent>()
+            .add_event::<GameOverEvent>()
             .configure_sets(
                 Update,
-                (
-                    TetrisSet::Spawn,
-                    TetrisSet::Movement,
-                    TetrisSet::Collision,
-                    TetrisSet::Visibility,
-                )
-                    .chain(),
+                (TetrisSet::Spawn, TetrisSet::Movement, TetrisSet::Collision).chain(),
             )
             .add_systems(
                 OnEnter(AppState::GameState),
-                (systems::setup_game, systems::clear_pieces),
+                (systems::clear_pieces, systems::setup_game).chain(),
             )
             .add_systems(
                 OnTransition {
                     entered: GameState::Play,
                     exited: GameState::GameOver,
                 },
-                (systems::setup_game, systems::clear_pieces),
+                (systems::clear_pieces, systems::setup_game).chain(),
+            )
+            .add_systems(
+                OnTransition {
+                    entered: GameState::Play,
+                    exited: GameState::Results,
+                },
+                (systems::clear_pieces, systems::setup_game).chain(),
             )
             .add_systems(
                 Update,
@@ -56,7 +72,15 @@ impl Plugin for TetrisPiecePlugin {
             )
             .add_systems(
                 Update,
-                (systems::rotate_piece, systems::move_piece)
+                (
+                    systems::hold_piece.run_if(
+                        input_just_pressed(KeyCode::KeyC)
+                            .or_else(input_just_pressed(KeyCode::ShiftLeft)),
+                    ),
+                    systems::rotate_piece,
+                    systems::move_piece,
+                    systems::hard_drop,
+                )
                     .chain()
                     .in_set(TetrisSet::Movement)
                     .run_if(in_state(GameState::Play)),
@@ -66,17 +90,12 @@ impl Plugin for TetrisPiecePlugin {
                 (
                     systems::collisions_check,
                     systems::remove_lines,
+                    systems::rise_garbage.run_if(resource_exists::<resources::RisingGarbage>),
                     systems::game_over_check,
                 )
                     .chain()
                     .in_set(TetrisSet::Collision)
                     .run_if(in_state(GameState::Play)),
-            )
-            .add_systems(
-                Update,
-                systems::visibility_control
-                    .in_set(TetrisSet::Visibility)
-                    .run_if(in_state(AppState::GameState)),
             );
     }
 }
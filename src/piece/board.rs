@@ -0,0 +1,685 @@
+use super::components::{Block, Piece, PieceType, Rotation, Turn};
+
+/// How a T piece was locked, following the 3-corner rule
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TSpin {
+    #[default]
+    None,
+    /// Three corners are occupied, but not both corners in front of the T
+    Mini,
+    /// Three corners are occupied, including both corners in front of the T
+    Full,
+}
+
+/// What left a static block in a cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cell {
+    Piece(PieceType),
+    /// A garbage row, added by the game and not by the player
+    Garbage,
+}
+
+/// The condition that ended the game
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopOut {
+    /// A new piece overlapped the stack when it spawned
+    BlockOut,
+    /// A piece locked entirely above the cup
+    LockOut,
+    /// A piece locked with some of its blocks above the cup, only when the rule is enabled
+    PartialLockOut,
+    /// The rising garbage pushed the stack above the board
+    GarbageOut,
+}
+
+impl TopOut {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TopOut::BlockOut => "BLOCK OUT",
+            TopOut::LockOut => "LOCK OUT",
+            TopOut::PartialLockOut => "PARTIAL LOCK OUT",
+            TopOut::GarbageOut => "GARBAGE OUT",
+        }
+    }
+}
+
+/// The rules of the game, without any dependency on the ECS.
+///
+/// Keeps an occupancy grid with the static blocks and the piece that is currently falling.
+/// The systems only ask the board if a movement is valid and then mirror the result into
+/// the block sprites.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Board {
+    width: i32,
+    /// The rows inside the cup
+    height: i32,
+    /// The rows above the cup, where the pieces spawn
+    buffer: i32,
+    cells: Vec<Option<Cell>>,
+    active: Option<Piece>,
+    /// The wall kick of the last rotation, while it is the last successful action of the piece
+    last_kick: Option<usize>,
+    /// Locking any block above the cup ends the game
+    partial_lock_out: bool,
+    top_out: Option<TopOut>,
+}
+
+impl Board {
+    /// Create an empty board with the given size and extra rows above it
+    pub fn new(width: usize, height: usize, buffer: usize) -> Self {
+        Self {
+            width: width as i32,
+            height: height as i32,
+            buffer: buffer as i32,
+            cells: vec![None; width * (height + buffer)],
+            active: None,
+            last_kick: None,
+            partial_lock_out: false,
+            top_out: None,
+        }
+    }
+
+    /// Enable the optional partial lock out rule
+    pub fn with_partial_lock_out(mut self, enabled: bool) -> Self {
+        self.partial_lock_out = enabled;
+        self
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// All the rows, including the ones above the cup
+    pub fn total_height(&self) -> i32 {
+        self.height + self.buffer
+    }
+
+    /// Convert a grid position to an index in the cells, if it is inside the board
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || x >= self.width || y < 0 || y >= self.total_height() {
+            return None;
+        }
+        Some((y * self.width + x) as usize)
+    }
+
+    /// Get what left a static block in the cell
+    pub fn get(&self, x: i32, y: i32) -> Option<Cell> {
+        self.index(x, y).and_then(|i| self.cells[i])
+    }
+
+    /// Check if the cell is inside the board and not occupied
+    pub fn is_free(&self, x: i32, y: i32) -> bool {
+        self.index(x, y).is_some_and(|i| self.cells[i].is_none())
+    }
+
+    /// Check if all the blocks are in free cells
+    pub fn fits(&self, blocks: &[Block]) -> bool {
+        blocks.iter().all(|b| self.is_free(b.x(), b.y()))
+    }
+
+    /// The condition that ended the game, if any
+    pub fn top_out(&self) -> Option<TopOut> {
+        self.top_out
+    }
+
+    /// The piece that is currently falling
+    pub fn active(&self) -> Option<&Piece> {
+        self.active.as_ref()
+    }
+
+    /// Place a new piece at the spawn position, just above the cup.
+    ///
+    /// Like in the guideline, the piece moves down one row right away if it can, so it shows
+    /// in the cup from the start.
+    /// Returns `false` if the piece overlaps the static blocks, a block out.
+    pub fn spawn(&mut self, piece_type: PieceType) -> bool {
+        let piece = Piece::spawn(piece_type, self.width, self.height);
+        let fits = self.fits(&piece.blocks());
+        self.active = Some(piece);
+        self.last_kick = None;
+        if fits {
+            self.move_down();
+        } else {
+            self.top_out = Some(TopOut::BlockOut);
+        }
+        fits
+    }
+
+    /// Try to replace the active piece, only if the new one fits
+    fn try_replace(&mut self, piece: Piece) -> bool {
+        if !self.fits(&piece.blocks()) {
+            return false;
+        }
+        self.active = Some(piece);
+        true
+    }
+
+    /// Try to move the active piece by the given offset
+    pub fn try_move(&mut self, dx: i32, dy: i32) -> bool {
+        let moved = match self.active {
+            Some(piece) => self.try_replace(piece.translated(dx, dy)),
+            None => false,
+        };
+        if moved {
+            self.last_kick = None;
+        }
+        moved
+    }
+
+    pub fn move_down(&mut self) -> bool {
+        self.try_move(0, -1)
+    }
+
+    /// Try to turn the active piece.
+    ///
+    /// Tests the SRS wall kicks in order and keeps the first one that fits.
+    pub fn rotate(&mut self, turn: Turn) -> bool {
+        let Some(piece) = self.active else {
+            return false;
+        };
+        let rotated = piece.rotated(turn);
+        let kick = piece
+            .piece_type()
+            .kicks(piece.rotation(), rotated.rotation())
+            .iter()
+            .position(|&[dx, dy]| self.try_replace(rotated.translated(dx, dy)));
+        if kick.is_some() {
+            self.last_kick = kick;
+        }
+        kick.is_some()
+    }
+
+    /// Classify the active piece with the 3-corner rule, as if it was locked now.
+    ///
+    /// Only a T piece whose last successful action was a rotation can spin. The walls and the
+    /// floor count as occupied corners.
+    pub fn t_spin(&self) -> TSpin {
+        let (Some(piece), Some(kick)) = (self.active, self.last_kick) else {
+            return TSpin::None;
+        };
+        if piece.piece_type() != PieceType::T {
+            return TSpin::None;
+        }
+
+        let [x, y] = piece.center();
+        let occupied = |&[dx, dy]: &[i32; 2]| !self.is_free(x + dx, y + dy);
+        let corners = [[-1, 1], [1, 1], [1, -1], [-1, -1]];
+        if corners.iter().filter(|c| occupied(c)).count() < 3 {
+            return TSpin::None;
+        }
+
+        // The corners on the side the T points to
+        let front = match piece.rotation() {
+            Rotation::Spawn => [[-1, 1], [1, 1]],
+            Rotation::Right => [[1, 1], [1, -1]],
+            Rotation::Reverse => [[1, -1], [-1, -1]],
+            Rotation::Left => [[-1, -1], [-1, 1]],
+        };
+        // The last kick moves the piece so far that it always counts as a full spin
+        if front.iter().all(occupied) || kick == 4 {
+            TSpin::Full
+        } else {
+            TSpin::Mini
+        }
+    }
+
+    /// Get the active piece moved down as far as it can go
+    pub fn landing(&self) -> Option<Piece> {
+        let mut piece = self.active?;
+        while self.fits(&piece.translated(0, -1).blocks()) {
+            piece = piece.translated(0, -1);
+        }
+        Some(piece)
+    }
+
+    /// Move the active piece down as far as it can go.
+    ///
+    /// Returns the number of rows the piece fell.
+    pub fn hard_drop(&mut self) -> u64 {
+        let mut rows = 0;
+        while self.move_down() {
+            rows += 1;
+        }
+        rows
+    }
+
+    /// Check if the active piece is resting on the floor or on a static block
+    pub fn is_grounded(&self) -> bool {
+        self.active
+            .is_some_and(|piece| !self.fits(&piece.translated(0, -1).blocks()))
+    }
+
+    /// Lock the active piece into the grid, making its blocks static.
+    ///
+    /// Returns the blocks of the locked piece.
+    pub fn lock(&mut self) -> Option<[Block; 4]> {
+        let piece = self.active.take()?;
+        let blocks = piece.blocks();
+        let above = blocks.iter().filter(|b| b.y() >= self.height).count();
+        if above == blocks.len() {
+            self.top_out = Some(TopOut::LockOut);
+        } else if above > 0 && self.partial_lock_out {
+            self.top_out = Some(TopOut::PartialLockOut);
+        }
+        for block in blocks.iter() {
+            if let Some(i) = self.index(block.x(), block.y()) {
+                self.cells[i] = Some(Cell::Piece(piece.piece_type()));
+            }
+        }
+        Some(blocks)
+    }
+
+    /// Check if every cell of the row is occupied
+    pub fn is_row_full(&self, y: i32) -> bool {
+        (0..self.width).all(|x| self.get(x, y).is_some())
+    }
+
+    /// Check if there are no static blocks left
+    pub fn is_empty(&self) -> bool {
+        self.cells.iter().all(Option::is_none)
+    }
+
+    /// Fill a row with garbage, leaving a hole in one column
+    pub fn fill_garbage_row(&mut self, y: i32, hole: i32) {
+        for x in (0..self.width).filter(|&x| x != hole) {
+            if let Some(i) = self.index(x, y) {
+                self.cells[i] = Some(Cell::Garbage);
+            }
+        }
+    }
+
+    /// How many rows still have some garbage
+    pub fn garbage_rows(&self) -> usize {
+        self.cells
+            .chunks(self.width as usize)
+            .filter(|row| row.contains(&Some(Cell::Garbage)))
+            .count()
+    }
+
+    /// Insert empty rows at the bottom and push everything above them up, the reverse of
+    /// a line clear. The active piece is pushed up too when the stack reaches it.
+    pub fn insert_rows_below(&mut self, count: i32) {
+        let count = count.clamp(0, self.total_height());
+        let kept = self.cells.len() - (count * self.width) as usize;
+        if self.cells[kept..].iter().any(Option::is_some) {
+            self.top_out = Some(TopOut::GarbageOut);
+        }
+        self.cells.truncate(kept);
+        self.cells.splice(
+            0..0,
+            std::iter::repeat_n(None, (count * self.width) as usize),
+        );
+
+        if let Some(mut piece) = self.active {
+            for _ in 0..count {
+                if self.fits(&piece.blocks()) {
+                    break;
+                }
+                piece = piece.translated(0, 1);
+            }
+            self.active = Some(piece);
+        }
+    }
+
+    /// Remove the bottom rows and move the rows above them down, leaving the active piece
+    /// where it is. The board can be played again after a top out.
+    ///
+    /// Returns the removed rows, sorted from the bottom to the top.
+    pub fn clear_bottom_rows(&mut self, count: i32) -> Vec<i32> {
+        let count = count.clamp(0, self.total_height());
+        self.cells.drain(..(count * self.width) as usize);
+        self.cells
+            .resize((self.width * self.total_height()) as usize, None);
+        self.top_out = None;
+        (0..count).collect()
+    }
+
+    /// Remove the full rows and move the rows above them down.
+    ///
+    /// Returns the removed rows, sorted from the bottom to the top.
+    pub fn clear_lines(&mut self) -> Vec<i32> {
+        let full_rows = (0..self.total_height())
+            .filter(|&y| self.is_row_full(y))
+            .collect::<Vec<_>>();
+        if full_rows.is_empty() {
+            return full_rows;
+        }
+
+        let mut cells = Vec::with_capacity(self.cells.len());
+        for (y, row) in self.cells.chunks(self.width as usize).enumerate() {
+            if !full_rows.contains(&(y as i32)) {
+                cells.extend_from_slice(row);
+            }
+        }
+        cells.resize(self.cells.len(), None);
+        self.cells = cells;
+        full_rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::components::PIECES;
+
+    /// The smallest and the biggest column of the active piece
+    fn columns(board: &Board) -> (i32, i32) {
+        let blocks = board.active().unwrap().blocks();
+        let xs = blocks.iter().map(Block::x);
+        (xs.clone().min().unwrap(), xs.max().unwrap())
+    }
+
+    #[test]
+    fn spawned_pieces_show_in_the_cup() {
+        for piece_type in PIECES {
+            let mut board = Board::new(10, 20, 20);
+            assert!(board.spawn(piece_type));
+            let blocks = board.active().unwrap().blocks();
+            assert!(
+                blocks.iter().any(|b| b.y() < 20),
+                "{piece_type:?} is hidden"
+            );
+            assert!(blocks.iter().all(|b| b.y() >= 19));
+        }
+
+        // Without room to move down, the piece stays above the cup
+        let mut board = Board::new(10, 20, 20);
+        board.cells[19 * 10 + 4] = Some(Cell::Piece(PieceType::I));
+        assert!(board.spawn(PieceType::T));
+        assert!(board.active().unwrap().blocks().iter().all(|b| b.y() >= 20));
+    }
+
+    #[test]
+    fn moves_stop_at_the_walls_and_the_stack() {
+        let mut board = Board::new(10, 20, 20);
+        board.spawn(PieceType::T);
+        while board.try_move(-1, 0) {}
+        assert_eq!(columns(&board).0, 0);
+        while board.try_move(1, 0) {}
+        assert_eq!(columns(&board).1, 9);
+
+        // A column of blocks stops the piece next to it
+        let mut board = Board::new(10, 20, 20);
+        for y in 0..40 {
+            board.cells[y * 10 + 7] = Some(Cell::Piece(PieceType::I));
+        }
+        board.spawn(PieceType::T);
+        while board.try_move(1, 0) {}
+        assert_eq!(columns(&board).1, 6);
+    }
+
+    #[test]
+    fn lock_makes_the_grounded_piece_static() {
+        let mut board = Board::new(10, 20, 20);
+        board.spawn(PieceType::O);
+        assert!(!board.is_grounded());
+        assert_eq!(board.hard_drop(), 19);
+        assert!(board.is_grounded());
+
+        let blocks = board.lock().unwrap();
+        assert!(board.active().is_none());
+        assert!(!board.is_grounded());
+        for block in blocks {
+            assert_eq!(
+                board.get(block.x(), block.y()),
+                Some(Cell::Piece(PieceType::O))
+            );
+        }
+        // The next piece rests on top of it
+        board.spawn(PieceType::O);
+        board.hard_drop();
+        assert!(board.active().unwrap().blocks().iter().all(|b| b.y() >= 2));
+    }
+
+    #[test]
+    fn clear_lines_moves_the_rows_above_down() {
+        let mut board = Board::new(10, 20, 20);
+        for x in 0..10 {
+            board.cells[x] = Some(Cell::Piece(PieceType::I));
+            board.cells[20 + x] = Some(Cell::Piece(PieceType::I));
+        }
+        board.cells[10 + 1] = Some(Cell::Piece(PieceType::J));
+        board.cells[30 + 2] = Some(Cell::Piece(PieceType::L));
+
+        assert_eq!(board.clear_lines(), vec![0, 2]);
+        // The row 1 moves down by one and the row 3 by two
+        assert_eq!(board.get(1, 0), Some(Cell::Piece(PieceType::J)));
+        assert_eq!(board.get(2, 1), Some(Cell::Piece(PieceType::L)));
+        assert_eq!(board.cells.iter().flatten().count(), 2);
+        assert!(board.clear_lines().is_empty());
+    }
+
+    /// A T-spin double slot, with the T already turned into it
+    fn t_slot() -> Board {
+        let mut board = Board::new(10, 20, 4);
+        for x in 0..10 {
+            if x != 4 {
+                board.cells[x] = Some(Cell::Piece(PieceType::I));
+            }
+            if !(3..=5).contains(&x) {
+                board.cells[10 + x] = Some(Cell::Piece(PieceType::I));
+            }
+        }
+        // The overhang above the slot
+        board.cells[20 + 3] = Some(Cell::Piece(PieceType::I));
+        board.active = Some(
+            Piece::spawn(PieceType::T, 10, 20)
+                .rotated(Turn::Half)
+                .translated(0, -19),
+        );
+        board.last_kick = Some(0);
+        board
+    }
+
+    #[test]
+    fn t_kicks_off_the_left_wall() {
+        let mut board = Board::new(10, 20, 20);
+        board.spawn(PieceType::T);
+        board.rotate(Turn::Clockwise);
+        while board.try_move(-1, 0) {}
+        assert_eq!(columns(&board), (0, 1));
+
+        // The flat T doesn't fit against the wall, the second test moves it right
+        assert!(board.rotate(Turn::CounterClockwise));
+        assert_eq!(board.active().unwrap().rotation(), Rotation::Spawn);
+        assert_eq!(board.last_kick, Some(1));
+        assert_eq!(columns(&board), (0, 2));
+    }
+
+    #[test]
+    fn i_kicks_off_both_walls() {
+        // Standing against the right wall, moving left
+        let mut board = Board::new(10, 20, 20);
+        board.spawn(PieceType::I);
+        board.rotate(Turn::Clockwise);
+        while board.try_move(1, 0) {}
+        assert_eq!(columns(&board), (9, 9));
+        assert!(board.rotate(Turn::CounterClockwise));
+        assert_eq!(board.last_kick, Some(2));
+        assert_eq!(columns(&board), (6, 9));
+
+        // Standing against the left wall, moving right
+        let mut board = Board::new(10, 20, 20);
+        board.spawn(PieceType::I);
+        board.rotate(Turn::CounterClockwise);
+        while board.try_move(-1, 0) {}
+        assert_eq!(columns(&board), (0, 0));
+        assert!(board.rotate(Turn::Clockwise));
+        assert_eq!(board.last_kick, Some(1));
+        assert_eq!(columns(&board), (0, 3));
+    }
+
+    #[test]
+    fn t_kicks_up_from_the_floor() {
+        let mut board = Board::new(10, 20, 20);
+        // The T stands on the floor pointing right, with the center at (4, 1)
+        board.active = Some(
+            Piece::spawn(PieceType::T, 10, 20)
+                .rotated(Turn::Clockwise)
+                .translated(0, -19),
+        );
+        assert_eq!(board.active().unwrap().center(), [4, 1]);
+        // Blocks on both sides leave only the kick two rows up
+        board.cells[10 + 3] = Some(Cell::Piece(PieceType::I));
+        board.cells[10 + 6] = Some(Cell::Piece(PieceType::I));
+
+        assert!(board.rotate(Turn::Clockwise));
+        assert_eq!(board.last_kick, Some(3));
+        let piece = board.active().unwrap();
+        assert_eq!(piece.rotation(), Rotation::Reverse);
+        assert_eq!(piece.center(), [4, 3]);
+    }
+
+    #[test]
+    fn rotation_fails_when_no_kick_fits() {
+        let mut board = Board::new(10, 20, 20);
+        let piece = Piece::spawn(PieceType::T, 10, 20).translated(0, -10);
+        let blocks = piece.blocks();
+        for y in 0..40 {
+            for x in 0..10 {
+                if !blocks.contains(&Block::new(x, y)) {
+                    board.cells[(y * 10 + x) as usize] = Some(Cell::Piece(PieceType::I));
+                }
+            }
+        }
+        board.active = Some(piece);
+
+        for turn in [Turn::Clockwise, Turn::CounterClockwise, Turn::Half] {
+            assert!(!board.rotate(turn));
+            assert_eq!(board.active(), Some(&piece));
+            assert_eq!(board.last_kick, None);
+        }
+    }
+
+    #[test]
+    fn four_turns_return_to_the_start() {
+        for piece_type in PIECES {
+            let mut board = Board::new(10, 20, 20);
+            board.spawn(piece_type);
+            board.try_move(0, -10);
+            let start = board.active().unwrap().blocks();
+            for _ in 0..4 {
+                assert!(board.rotate(Turn::Clockwise));
+                assert_eq!(board.last_kick, Some(0));
+            }
+            assert_eq!(board.active().unwrap().blocks(), start);
+        }
+    }
+
+    #[test]
+    fn t_spin_follows_the_three_corner_rule() {
+        let board = t_slot();
+        assert_eq!(board.t_spin(), TSpin::Full);
+
+        // Without a rotation as the last action it is only a drop
+        let mut board = t_slot();
+        board.last_kick = None;
+        assert_eq!(board.t_spin(), TSpin::None);
+
+        // Two corners are not enough
+        let mut board = t_slot();
+        board.cells[20 + 3] = None;
+        assert_eq!(board.t_spin(), TSpin::None);
+
+        let mut board = t_slot();
+        board.lock();
+        assert_eq!(board.clear_lines(), vec![0, 1]);
+    }
+
+    #[test]
+    fn top_out_follows_the_guideline() {
+        // A piece that can't move down after it spawns is entirely above the cup
+        let mut board = Board::new(10, 20, 20);
+        board.cells[19 * 10 + 4] = Some(Cell::Piece(PieceType::I));
+        board.spawn(PieceType::O);
+        board.lock();
+        assert_eq!(board.top_out(), Some(TopOut::LockOut));
+
+        // The next piece can't even spawn on top of it
+        assert!(!board.spawn(PieceType::O));
+        assert_eq!(board.top_out(), Some(TopOut::BlockOut));
+
+        // Partially above the cup is fine, unless the optional rule is enabled
+        for partial_lock_out in [false, true] {
+            let mut board = Board::new(10, 20, 20).with_partial_lock_out(partial_lock_out);
+            for y in 0..19 {
+                for x in 3..6 {
+                    board.cells[y * 10 + x] = Some(Cell::Piece(PieceType::I));
+                }
+            }
+            // The T lands on the top row, with the middle block sticking out
+            board.spawn(PieceType::T);
+            board.hard_drop();
+            board.lock();
+            let expected = partial_lock_out.then_some(TopOut::PartialLockOut);
+            assert_eq!(board.top_out(), expected);
+        }
+    }
+
+    #[test]
+    fn clearing_the_bottom_rows_makes_room_after_a_top_out() {
+        let mut board = Board::new(10, 20, 20);
+        for y in 0..20 {
+            board.cells[y * 10] = Some(Cell::Piece(PieceType::I));
+        }
+        board.cells[5] = Some(Cell::Piece(PieceType::O));
+        board.cells[19 * 10 + 4] = Some(Cell::Piece(PieceType::I));
+        board.spawn(PieceType::O);
+        board.lock();
+        assert_eq!(board.top_out(), Some(TopOut::LockOut));
+
+        assert_eq!(board.clear_bottom_rows(10), (0..10).collect::<Vec<_>>());
+        assert_eq!(board.top_out(), None);
+        assert_eq!(board.get(5, 0), None);
+        assert_eq!(board.get(0, 9), Some(Cell::Piece(PieceType::I)));
+        assert_eq!(board.get(0, 10), None);
+        // The locked piece came down with the rest of the stack
+        assert_eq!(board.get(4, 10), Some(Cell::Piece(PieceType::O)));
+        assert!(board.spawn(PieceType::O));
+    }
+
+    #[test]
+    fn garbage_rows_have_one_hole() {
+        let mut board = Board::new(10, 20, 20);
+        board.fill_garbage_row(0, 3);
+        board.fill_garbage_row(1, 7);
+        assert_eq!(board.garbage_rows(), 2);
+        assert_eq!(board.get(3, 0), None);
+        assert_eq!(board.get(4, 0), Some(Cell::Garbage));
+        assert!(!board.is_row_full(0));
+
+        // Filling the hole of the bottom row clears it
+        board.cells[3] = Some(Cell::Piece(PieceType::I));
+        assert_eq!(board.clear_lines(), vec![0]);
+        assert_eq!(board.garbage_rows(), 1);
+    }
+
+    #[test]
+    fn rows_inserted_below_push_the_stack_up() {
+        let mut board = Board::new(10, 20, 20);
+        board.fill_garbage_row(0, 3);
+        board.spawn(PieceType::O);
+        board.hard_drop();
+        let landed = board.active().unwrap().blocks();
+
+        board.insert_rows_below(1);
+        board.fill_garbage_row(0, 5);
+        assert_eq!(board.get(3, 1), None);
+        assert_eq!(board.get(5, 1), Some(Cell::Garbage));
+        assert_eq!(board.get(5, 0), None);
+        // The piece resting on the stack moves up with it
+        let pushed = board.active().unwrap().blocks();
+        assert!(landed.iter().zip(pushed).all(|(a, b)| b.y() == a.y() + 1));
+
+        // Pushing a block above the board tops out
+        let mut board = Board::new(10, 20, 20);
+        board.cells[39 * 10] = Some(Cell::Garbage);
+        board.insert_rows_below(1);
+        assert_eq!(board.top_out(), Some(TopOut::GarbageOut));
+    }
+}
@@ -1,7 +1,7 @@
 use bevy::color::palettes::css::*;
 use bevy::prelude::*;
 
-use crate::common::{BLOCK_SIZE, BLOCK_SPRITE_SIZE, BOARD_CENTER_X, BOARD_CENTER_Y};
+use crate::common::{BLOCK_SIZE, BLOCK_SPRITE_SIZE};
 use crate::state::AppState;
 
 #[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
@@ -15,23 +15,6 @@ impl Block {
         Self { x, y }
     }
 
-    pub fn move_down(&mut self) {
-        self.y -= 1;
-    }
-
-    pub fn move_left(&mut self) {
-        self.x -= 1;
-    }
-
-    pub fn move_right(&mut self) {
-        self.x += 1;
-    }
-
-    pub fn move_to(&mut self, x: i32, y: i32) {
-        self.x = x;
-        self.y = y;
-    }
-
     pub fn x(&self) -> i32 {
         self.x
     }
@@ -53,20 +36,29 @@ impl Block {
             .with_scale(Vec3::splat(BLOCK_SPRITE_SIZE))
     }
 
+    /// The position of the block in the cup, the bottom left cell is at the origin
     pub fn as_board_translation(&self) -> Vec3 {
         Vec3::new(
-            (self.x as f32 - BOARD_CENTER_X + 0.5) * BLOCK_SIZE,
-            (self.y as f32 - BOARD_CENTER_Y + 0.5) * BLOCK_SIZE,
+            (self.x as f32 + 0.5) * BLOCK_SIZE,
+            (self.y as f32 + 0.5) * BLOCK_SIZE,
             0.0,
         )
     }
     pub fn as_board_transform(&self) -> Transform {
-        // The origin is the center top of the board
         Transform::from_translation(self.as_board_translation())
             .with_scale(Vec3::splat(BLOCK_SPRITE_SIZE))
     }
+
+    /// Same as the board translation, but behind the blocks so the piece is drawn over its ghost
+    pub fn as_ghost_translation(&self) -> Vec3 {
+        self.as_board_translation().with_z(-1.0)
+    }
 }
 
+/// Marks the blocks that preview where the falling piece will land
+#[derive(Component, Debug, Clone, Copy, Reflect)]
+pub struct GhostBlock;
+
 #[derive(Component, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Reflect)]
 pub enum PieceType {
     I,
@@ -104,7 +96,7 @@ const SHAPE_S: [[i32; 2]; 4] = [[3, 0], [4, 0], [4, 1], [5, 1]];
 
 ///  #
 /// ###
-const SHAPE_T: [[i32; 2]; 4] = [[3, 0], [3, 1], [4, 1], [5, 1]];
+const SHAPE_T: [[i32; 2]; 4] = [[3, 0], [4, 0], [5, 0], [4, 1]];
 
 /// ##
 ///  ##
@@ -123,46 +115,90 @@ pub fn select_piece(piece_type: PieceType) -> [Block; 4] {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct Movable {
-    pub down: bool,
-    pub left: bool,
-    pub right: bool,
+/// The four rotation states of the Super Rotation System
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    /// The state the pieces spawn in (0)
+    #[default]
+    Spawn,
+    /// One clockwise turn from the spawn state (R)
+    Right,
+    /// Two turns from the spawn state (2)
+    Reverse,
+    /// One counter-clockwise turn from the spawn state (L)
+    Left,
 }
 
-impl Movable {
-    /// Create a new Movable struct with all the movements enabled
-    pub fn new() -> Self {
-        Self {
-            down: true,
-            left: true,
-            right: true,
+impl Rotation {
+    /// The state after a clockwise turn
+    pub fn clockwise(&self) -> Self {
+        match self {
+            Rotation::Spawn => Rotation::Right,
+            Rotation::Right => Rotation::Reverse,
+            Rotation::Reverse => Rotation::Left,
+            Rotation::Left => Rotation::Spawn,
         }
     }
 
-    /// Check if the piece can rotate
-    ///
-    /// All the movements should be enabled for rotation
-    pub fn can_rotate(&self) -> bool {
-        self.down && self.left && self.right
-    }
-
-    /// Check if the piece can move down
-    pub fn can_move_down(&self) -> bool {
-        self.down
+    /// The state after a counter-clockwise turn
+    pub fn counter_clockwise(&self) -> Self {
+        match self {
+            Rotation::Spawn => Rotation::Left,
+            Rotation::Right => Rotation::Spawn,
+            Rotation::Reverse => Rotation::Right,
+            Rotation::Left => Rotation::Reverse,
+        }
     }
 
-    /// Check if the piece can move left
-    pub fn can_move_left(&self) -> bool {
-        self.left
+    /// The state after the given turn
+    pub fn turn(&self, turn: Turn) -> Self {
+        match turn {
+            Turn::Clockwise => self.clockwise(),
+            Turn::CounterClockwise => self.counter_clockwise(),
+            Turn::Half => self.clockwise().clockwise(),
+        }
     }
+}
 
-    /// Check if the piece can move right
-    pub fn can_move_right(&self) -> bool {
-        self.right
-    }
+/// The ways the player can turn a piece
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Turn {
+    Clockwise,
+    CounterClockwise,
+    /// A 180° turn, it has no wall kicks so it is only tested in place
+    Half,
 }
 
+// The SRS wall kicks, as offsets to test in order until one fits.
+// Each row is a rotation: 0->R, R->0, R->2, 2->R, 2->L, L->2, L->0, 0->L
+
+/// Wall kicks for the J, L, S, T and Z pieces
+const KICKS_JLSTZ: [[[i32; 2]; 5]; 8] = [
+    [[0, 0], [-1, 0], [-1, 1], [0, -2], [-1, -2]],
+    [[0, 0], [1, 0], [1, -1], [0, 2], [1, 2]],
+    [[0, 0], [1, 0], [1, -1], [0, 2], [1, 2]],
+    [[0, 0], [-1, 0], [-1, 1], [0, -2], [-1, -2]],
+    [[0, 0], [1, 0], [1, 1], [0, -2], [1, -2]],
+    [[0, 0], [-1, 0], [-1, -1], [0, 2], [-1, 2]],
+    [[0, 0], [-1, 0], [-1, -1], [0, 2], [-1, 2]],
+    [[0, 0], [1, 0], [1, 1], [0, -2], [1, -2]],
+];
+
+/// Wall kicks for the I piece
+const KICKS_I: [[[i32; 2]; 5]; 8] = [
+    [[0, 0], [-2, 0], [1, 0], [-2, -1], [1, 2]],
+    [[0, 0], [2, 0], [-1, 0], [2, 1], [-1, -2]],
+    [[0, 0], [-1, 0], [2, 0], [-1, 2], [2, -1]],
+    [[0, 0], [1, 0], [-2, 0], [1, -2], [-2, 1]],
+    [[0, 0], [2, 0], [-1, 0], [2, 1], [-1, -2]],
+    [[0, 0], [-2, 0], [1, 0], [-2, -1], [1, 2]],
+    [[0, 0], [1, 0], [-2, 0], [1, -2], [-2, 1]],
+    [[0, 0], [-1, 0], [2, 0], [-1, 2], [2, -1]],
+];
+
+/// The O piece doesn't need to kick, it only tests the rotation in place
+const NO_KICKS: [[i32; 2]; 1] = [[0, 0]];
+
 #[derive(Bundle)]
 pub struct PieceBundle {
     sprite: SpriteBundle,
@@ -209,11 +245,53 @@ pub const PIECES: [PieceType; 7] = [
 ];
 
 impl PieceType {
-    /// Build a piece from the piece type
-    pub fn build(&self, commands: &mut Commands) {
-        let mut blocks = select_piece(*self);
-        for block in blocks.iter_mut() {
-            block.y += 20;
+    /// The bottom left corner and the size of the box where the piece rotates,
+    /// in the same coordinates as the shapes
+    fn rotation_box(&self) -> ([i32; 2], i32) {
+        match self {
+            PieceType::I => ([3, -2], 4),
+            PieceType::O => ([4, 0], 2),
+            _ => ([3, -1], 3),
+        }
+    }
+
+    /// Get the blocks of the piece in the given rotation state
+    pub fn shape(&self, rotation: Rotation) -> [Block; 4] {
+        let ([origin_x, origin_y], size) = self.rotation_box();
+        select_piece(*self).map(|mut block| {
+            // Turn clockwise inside the rotation box once per state
+            for _ in 0..rotation as i32 {
+                let (x, y) = (block.x - origin_x, block.y - origin_y);
+                block.x = origin_x + y;
+                block.y = origin_y + size - 1 - x;
+            }
+            block
+        })
+    }
+
+    /// Get the wall kicks to test when rotating from one state to the other
+    pub fn kicks(&self, from: Rotation, to: Rotation) -> &'static [[i32; 2]] {
+        let table = match self {
+            PieceType::I => &KICKS_I,
+            PieceType::O => return &NO_KICKS,
+            _ => &KICKS_JLSTZ,
+        };
+        match (from, to) {
+            (Rotation::Spawn, Rotation::Right) => &table[0],
+            (Rotation::Right, Rotation::Spawn) => &table[1],
+            (Rotation::Right, Rotation::Reverse) => &table[2],
+            (Rotation::Reverse, Rotation::Right) => &table[3],
+            (Rotation::Reverse, Rotation::Left) => &table[4],
+            (Rotation::Left, Rotation::Reverse) => &table[5],
+            (Rotation::Left, Rotation::Spawn) => &table[6],
+            (Rotation::Spawn, Rotation::Left) => &table[7],
+            _ => &NO_KICKS,
+        }
+    }
+
+    /// Build a piece from the piece type, with the blocks at the spawn position
+    pub fn build(&self, commands: &mut Commands, blocks: &[Block]) {
+        for block in blocks.iter() {
             commands
                 .spawn((
                     PieceBundle {
@@ -233,70 +311,92 @@ impl PieceType {
                 .insert(StateScoped(AppState::GameState));
         }
     }
+
+    /// Build the ghost of a piece, a translucent copy of the blocks at the landing position
+    pub fn build_ghost(&self, commands: &mut Commands, blocks: &[Block]) {
+        let color = Color::from(self).with_alpha(0.3);
+        for block in blocks.iter() {
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        ..Default::default()
+                    },
+                    transform: Transform::from_translation(block.as_ghost_translation())
+                        .with_scale(Vec3::splat(BLOCK_SPRITE_SIZE)),
+                    ..Default::default()
+                },
+                GhostBlock,
+                Name::new(format!("{:?} Ghost", self)),
+                StateScoped(AppState::GameState),
+            ));
+        }
+    }
 }
 
-/// A helper to help with the rotation of the pieces
+/// A helper to help with the movement and rotation of the pieces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Piece {
     piece_type: PieceType,
-    blocks: [Block; 4],
-    pivot_x: i32,
-    pivot_y: i32,
+    rotation: Rotation,
+    // Offset of the piece from the shape coordinates
+    x: i32,
+    y: i32,
 }
 
 impl Piece {
-    /// Create a new piece from an array of blocks
-    pub fn from_array(q_blocks: &[Block], piece_type: PieceType) -> Self {
-        let mut blocks = [Block::new(0, 0); 4];
-        for (i, block) in q_blocks.iter().enumerate() {
-            blocks[i] = *block;
-        }
-        let mut result = Self {
+    /// Create a new piece at the spawn position, above the top of a cup with the given size.
+    ///
+    /// The pieces are centered, rounding to the left, like the 3-wide pieces of a 10-wide cup.
+    pub fn spawn(piece_type: PieceType, width: i32, height: i32) -> Self {
+        Self {
             piece_type,
-            blocks,
-            pivot_x: 0,
-            pivot_y: 0,
-        };
-        result.compute();
-        result
+            rotation: Rotation::Spawn,
+            // The shapes start at the column 3
+            x: (width - 3).div_euclid(2) - 3,
+            y: height,
+        }
+    }
+
+    pub fn piece_type(&self) -> PieceType {
+        self.piece_type
+    }
+
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
     }
 
     pub fn blocks(&self) -> [Block; 4] {
-        self.blocks
+        self.piece_type
+            .shape(self.rotation)
+            .map(|block| Block::new(block.x + self.x, block.y + self.y))
     }
 
-    /// Compute the pivot and if the piece is rotated
-    fn compute(&mut self) {
-        let sum_x: i32 = self.blocks.iter().map(|b| b.x).sum();
-        let sum_y: i32 = self.blocks.iter().map(|b| b.y).sum();
-        // Average of the x and y coordinates of the blocks
-        self.pivot_x = sum_x / 4 - sum_y / 4;
-        self.pivot_y = sum_x / 4 + sum_y / 4;
+    /// The lowest row of the blocks
+    pub fn bottom(&self) -> i32 {
+        self.blocks().iter().map(Block::y).min().unwrap_or(self.y)
     }
 
-    /// Rotate the blocks of the piece
-    pub fn rotate_blocks(&self) -> [Block; 4] {
-        let mut blocks = self.blocks;
-        if self.piece_type == PieceType::O {
-            return blocks;
-        }
-        blocks.iter_mut().for_each(|block| {
-            self.rotate_block(block);
-        });
-        blocks
+    /// The cell in the middle of the rotation box, only a real cell for the 3x3 pieces
+    pub fn center(&self) -> [i32; 2] {
+        let ([origin_x, origin_y], size) = self.piece_type.rotation_box();
+        [origin_x + size / 2 + self.x, origin_y + size / 2 + self.y]
     }
 
-    /// Rotate a block
-    pub fn rotate_block(&self, block: &mut Block) {
-        if self.piece_type == PieceType::O {
-            return;
+    /// Get a copy of the piece moved by the given offset
+    pub fn translated(&self, dx: i32, dy: i32) -> Self {
+        Self {
+            x: self.x + dx,
+            y: self.y + dy,
+            ..*self
         }
-        let inv_x = -block.x;
-        block.x = block.y + self.pivot_x;
-        // Trial and error to find the correct formula
-        if matches!(self.piece_type, PieceType::L | PieceType::J) {
-            block.y = inv_x + self.pivot_y;
-        } else {
-            block.y = inv_x + self.pivot_y + 1;
+    }
+
+    /// Get a copy of the piece turned, without testing any wall kick
+    pub fn rotated(&self, turn: Turn) -> Self {
+        Self {
+            rotation: self.rotation.turn(turn),
+            ..*self
         }
     }
 }
@@ -0,0 +1,13 @@
+use bevy::prelude::*;
+
+use super::board::{TSpin, TopOut};
+
+/// Sent when the falling piece locks into the board
+#[derive(Debug, Clone, Copy, Event)]
+pub struct PieceLockedEvent {
+    pub t_spin: TSpin,
+}
+
+/// Sent when the game ends, with the condition that ended it
+#[derive(Debug, Clone, Copy, Event)]
+pub struct GameOverEvent(pub TopOut);
@@ -0,0 +1,126 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+use crate::{
+    common::{GARBAGE_COLOR, GARBAGE_HOLE_CHANGE},
+    state::AppState,
+};
+
+use super::{board::Board, components::Block};
+
+/// The stream of the random generator used for the garbage, the pieces use the stream 0
+const GARBAGE_STREAM: u64 = 1;
+
+/// Generates the garbage rows, full rows with one hole
+///
+/// The random numbers come from the seed of the game, so the same seed always gives the same rows.
+#[derive(Resource)]
+pub struct GarbageGenerator {
+    rng: ChaCha8Rng,
+    /// The chance the hole moves to another column on the next row
+    hole_change: f64,
+    hole: Option<i32>,
+}
+
+impl GarbageGenerator {
+    pub fn new(seed: u64, hole_change: f64) -> Self {
+        // The pieces use the same seed, so the garbage reads another stream to be independent
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        rng.set_stream(GARBAGE_STREAM);
+        Self {
+            rng,
+            // Not a number would make every roll fail, so it falls back to the default
+            hole_change: if hole_change.is_nan() {
+                GARBAGE_HOLE_CHANGE
+            } else {
+                hole_change.clamp(0.0, 1.0)
+            },
+            hole: None,
+        }
+    }
+
+    /// Pick the hole of the next row, in a board with the given width
+    pub fn next_hole(&mut self, width: i32) -> i32 {
+        let hole = match self.hole {
+            Some(hole) if width > 1 && self.rng.gen_bool(self.hole_change) => {
+                // Skip the current column, so the hole always moves
+                let next = self.rng.gen_range(0..width - 1);
+                if next >= hole {
+                    next + 1
+                } else {
+                    next
+                }
+            }
+            Some(hole) => hole,
+            None => self.rng.gen_range(0..width),
+        };
+        self.hole = Some(hole);
+        hole
+    }
+
+    /// Fill a row of the board with garbage and spawn its blocks.
+    ///
+    /// The blocks are static from the start, so they don't have a `PieceType`.
+    pub fn spawn_row(&mut self, commands: &mut Commands, board: &mut Board, y: i32) {
+        let hole = self.next_hole(board.width());
+        board.fill_garbage_row(y, hole);
+        for x in (0..board.width()).filter(|&x| x != hole) {
+            let block = Block::new(x, y);
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: GARBAGE_COLOR,
+                        ..Default::default()
+                    },
+                    transform: block.as_board_transform(),
+                    ..Default::default()
+                },
+                block,
+                Name::new("Garbage"),
+                StateScoped(AppState::GameState),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hole_moves_with_the_chosen_chance() {
+        let mut generator = GarbageGenerator::new(0, 1.0);
+        let mut last = generator.next_hole(10);
+        for _ in 0..1000 {
+            let hole = generator.next_hole(10);
+            assert!((0..10).contains(&hole));
+            assert_ne!(hole, last);
+            last = hole;
+        }
+
+        let mut generator = GarbageGenerator::new(0, 0.0);
+        let first = generator.next_hole(10);
+        assert!((0..1000).all(|_| generator.next_hole(10) == first));
+    }
+
+    #[test]
+    fn garbage_is_independent_from_the_pieces() {
+        let mut pieces = ChaCha8Rng::seed_from_u64(42);
+        let mut garbage = GarbageGenerator::new(42, 1.0);
+        let from_pieces = (0..8).map(|_| pieces.gen::<u64>()).collect::<Vec<_>>();
+        let from_garbage = (0..8).map(|_| garbage.rng.gen::<u64>()).collect::<Vec<_>>();
+        assert_ne!(from_pieces, from_garbage);
+    }
+
+    #[test]
+    fn invalid_chances_are_replaced() {
+        for (chance, expected) in [(f64::NAN, GARBAGE_HOLE_CHANGE), (-1.0, 0.0), (2.0, 1.0)] {
+            let mut generator = GarbageGenerator::new(0, chance);
+            assert_eq!(generator.hole_change, expected);
+            for _ in 0..100 {
+                generator.next_hole(10);
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use std::str::FromStr;
+
+use bevy::prelude::*;
+
+/// The time of a frame, gravity is measured in rows per frame (G)
+const FRAME: f32 = 1.0 / 60.0;
+/// At 20G the piece falls to the stack as soon as it spawns
+pub const MAX_GRAVITY_SECONDS: f32 = FRAME / 20.0;
+/// The weakest gravity of a custom curve, one row per minute
+const MIN_GRAVITY_SECONDS: f32 = 60.0;
+
+/// Frames per row of the NES version, from level 0 to 29 and above
+const NES_FRAMES: [u32; 30] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+    1,
+];
+
+/// How fast the pieces fall at each level
+#[derive(Resource, Debug, Default, Clone, PartialEq)]
+pub enum GravityCurve {
+    /// The guideline formula, `(0.8 - (level - 1) * 0.007) ^ (level - 1)` seconds per row
+    #[default]
+    Guideline,
+    /// The NES table of frames per row, where our level 1 is the NES level 0
+    Nes,
+    /// Seconds per row for each level from 1, the last one is kept for the levels above
+    Custom(Vec<f32>),
+}
+
+impl GravityCurve {
+    /// The time the piece takes to fall one row, never faster than 20G or slower than a row
+    /// per minute
+    pub fn seconds_per_row(&self, level: u64) -> f32 {
+        let level = level.max(1);
+        let seconds = match self {
+            GravityCurve::Guideline => {
+                let n = (level - 1) as f32;
+                (0.8 - n * 0.007).max(0.0).powf(n)
+            }
+            GravityCurve::Nes => {
+                let frames = NES_FRAMES[((level - 1) as usize).min(NES_FRAMES.len() - 1)];
+                frames as f32 * FRAME
+            }
+            GravityCurve::Custom(seconds) => {
+                let index = ((level - 1) as usize).min(seconds.len().saturating_sub(1));
+                seconds.get(index).copied().unwrap_or(1.0)
+            }
+        };
+        seconds.clamp(MAX_GRAVITY_SECONDS, MIN_GRAVITY_SECONDS)
+    }
+}
+
+impl FromStr for GravityCurve {
+    type Err = String;
+
+    /// Parse `guideline`, `nes` or a list of seconds per row like `1.0,0.5,0.1`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "guideline" => Ok(GravityCurve::Guideline),
+            "nes" => Ok(GravityCurve::Nes),
+            _ => s
+                .split(',')
+                .map(|seconds| {
+                    let seconds = seconds.trim().parse::<f32>().map_err(|e| e.to_string())?;
+                    if (0.0..=MIN_GRAVITY_SECONDS).contains(&seconds) {
+                        Ok(seconds)
+                    } else {
+                        Err(format!(
+                            "{seconds} is not between 0 and {MIN_GRAVITY_SECONDS}"
+                        ))
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(GravityCurve::Custom)
+                .map_err(|e| format!("Invalid gravity curve {s}: {e}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gravity_gets_faster_with_the_level() {
+        let curves = [
+            GravityCurve::Guideline,
+            GravityCurve::Nes,
+            "1.0, 0.5, 0".parse().unwrap(),
+        ];
+        for curve in curves {
+            let mut last = f32::MAX;
+            for level in 1..100 {
+                let seconds = curve.seconds_per_row(level);
+                assert!(seconds <= last, "{curve:?} is slower at level {level}");
+                assert!(seconds >= MAX_GRAVITY_SECONDS);
+                last = seconds;
+            }
+        }
+        assert_eq!(GravityCurve::Guideline.seconds_per_row(1), 1.0);
+        assert_eq!(
+            GravityCurve::Guideline.seconds_per_row(20),
+            MAX_GRAVITY_SECONDS
+        );
+        assert_eq!(GravityCurve::Nes.seconds_per_row(30), FRAME);
+    }
+
+    #[test]
+    fn custom_gravity_rejects_invalid_seconds() {
+        for curve in ["inf", "1e20", "NaN", "-1", "1.0,x"] {
+            assert!(
+                curve.parse::<GravityCurve>().is_err(),
+                "{curve} was accepted"
+            );
+        }
+        let slow = GravityCurve::Custom(vec![f32::MAX]);
+        assert_eq!(slow.seconds_per_row(1), MIN_GRAVITY_SECONDS);
+    }
+}
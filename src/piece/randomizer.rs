@@ -0,0 +1,186 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+use super::components::{PieceType, PIECES};
+
+/// A strategy to pick the pieces of the game
+///
+/// The random numbers come from the queue, so the same seed always gives the same pieces.
+pub trait Randomizer: Send + Sync {
+    /// Pick the next piece
+    fn next(&mut self, rng: &mut ChaCha8Rng) -> PieceType;
+}
+
+/// The rulesets the player can choose from
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RandomizerKind {
+    /// Shuffled bags with one of each piece, the modern guideline
+    #[default]
+    SevenBag,
+    /// Shuffled bags with two of each piece
+    FourteenBag,
+    /// Every piece has the same chance, with no memory
+    PureRandom,
+    /// Rolls again once when the piece repeats the last one
+    Nes,
+    /// Avoids the last 4 pieces, rolling up to 6 times
+    Tgm,
+}
+
+impl RandomizerKind {
+    /// Create a new randomizer of this kind
+    pub fn build(&self) -> Box<dyn Randomizer> {
+        match self {
+            RandomizerKind::SevenBag => Box::new(Bag::new(1)),
+            RandomizerKind::FourteenBag => Box::new(Bag::new(2)),
+            RandomizerKind::PureRandom => Box::new(PureRandom),
+            RandomizerKind::Nes => Box::new(Nes::default()),
+            RandomizerKind::Tgm => Box::new(Tgm::new()),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RandomizerKind::SevenBag => "7-BAG",
+            RandomizerKind::FourteenBag => "14-BAG",
+            RandomizerKind::PureRandom => "RANDOM",
+            RandomizerKind::Nes => "NES",
+            RandomizerKind::Tgm => "TGM",
+        }
+    }
+
+    /// The kind after this one, to cycle through them in the menu
+    pub fn cycle(&self) -> Self {
+        match self {
+            RandomizerKind::SevenBag => RandomizerKind::FourteenBag,
+            RandomizerKind::FourteenBag => RandomizerKind::PureRandom,
+            RandomizerKind::PureRandom => RandomizerKind::Nes,
+            RandomizerKind::Nes => RandomizerKind::Tgm,
+            RandomizerKind::Tgm => RandomizerKind::SevenBag,
+        }
+    }
+}
+
+/// Takes the pieces from a shuffled bag, with a number of copies of each piece
+struct Bag {
+    copies: usize,
+    bag: Vec<PieceType>,
+}
+
+impl Bag {
+    fn new(copies: usize) -> Self {
+        Self {
+            copies,
+            bag: Vec::with_capacity(PIECES.len() * copies),
+        }
+    }
+}
+
+impl Randomizer for Bag {
+    fn next(&mut self, rng: &mut ChaCha8Rng) -> PieceType {
+        if self.bag.is_empty() {
+            for _ in 0..self.copies {
+                self.bag.extend(PIECES);
+            }
+            self.bag.shuffle(rng);
+            debug!("Generated pieces: {:?}", self.bag);
+        }
+        self.bag.pop().unwrap()
+    }
+}
+
+struct PureRandom;
+
+impl Randomizer for PureRandom {
+    fn next(&mut self, rng: &mut ChaCha8Rng) -> PieceType {
+        *PIECES.choose(rng).unwrap()
+    }
+}
+
+#[derive(Default)]
+struct Nes {
+    last: Option<PieceType>,
+}
+
+impl Randomizer for Nes {
+    fn next(&mut self, rng: &mut ChaCha8Rng) -> PieceType {
+        let mut piece = *PIECES.choose(rng).unwrap();
+        // Only one more roll, so repeats are rarer but still possible
+        if self.last == Some(piece) {
+            piece = *PIECES.choose(rng).unwrap();
+        }
+        self.last = Some(piece);
+        piece
+    }
+}
+
+/// How many pieces the TGM randomizer remembers
+const TGM_HISTORY: usize = 4;
+/// How many times the TGM randomizer rolls before keeping a piece from the history
+const TGM_ROLLS: usize = 6;
+/// The first piece is never one that forces an overhang
+const TGM_FIRST_PIECES: [PieceType; 4] = [PieceType::I, PieceType::J, PieceType::L, PieceType::T];
+
+struct Tgm {
+    history: [PieceType; TGM_HISTORY],
+    first: bool,
+}
+
+impl Tgm {
+    fn new() -> Self {
+        Self {
+            // The history starts with the pieces that are the hardest to place
+            history: [PieceType::Z, PieceType::S, PieceType::S, PieceType::Z],
+            first: true,
+        }
+    }
+}
+
+impl Randomizer for Tgm {
+    fn next(&mut self, rng: &mut ChaCha8Rng) -> PieceType {
+        let piece = if self.first {
+            self.first = false;
+            *TGM_FIRST_PIECES.choose(rng).unwrap()
+        } else {
+            let mut piece = *PIECES.choose(rng).unwrap();
+            for _ in 1..TGM_ROLLS {
+                if !self.history.contains(&piece) {
+                    break;
+                }
+                piece = *PIECES.choose(rng).unwrap();
+            }
+            piece
+        };
+        self.history.rotate_right(1);
+        self.history[0] = piece;
+        piece
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fourteen_bag_has_two_of_each_piece() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut randomizer = RandomizerKind::FourteenBag.build();
+        for _ in 0..1000 {
+            let mut counts = [0; 7];
+            for _ in 0..14 {
+                counts[randomizer.next(&mut rng) as usize] += 1;
+            }
+            assert_eq!(counts, [2; 7]);
+        }
+    }
+
+    #[test]
+    fn tgm_never_starts_with_s_z_or_o() {
+        for seed in 0..1000 {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let first = RandomizerKind::Tgm.build().next(&mut rng);
+            assert!(TGM_FIRST_PIECES.contains(&first));
+        }
+    }
+}
@@ -1,73 +1,458 @@
-use std::collections::VecDeque;
+use std::{collections::VecDeque, time::Duration};
 
 use bevy::prelude::*;
 use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
 
-use crate::common::CACHED_PIECES;
+use crate::common::{
+    AUTO_REPEAT_RATE, AUTO_SHIFT_DELAY, CACHED_PIECES, GARBAGE_HOLE_CHANGE, SOFT_DROP_FACTOR,
+};
 
-use super::components::{PieceType, PIECES};
+use super::{
+    board::{Board, TSpin},
+    components::PieceType,
+    randomizer::{Randomizer, RandomizerKind},
+};
 
 #[derive(Resource)]
 pub struct MoveDownTimer(pub Timer);
 
+/// Time a piece can rest on the stack before it locks, so it can still slide or spin
 #[derive(Resource)]
-pub struct ManualMoveTimer(pub Timer);
+pub struct LockDelay {
+    pub timer: Timer,
+    /// How many times the player restarted the timer of the current piece
+    pub resets: u32,
+    pub max_resets: u32,
+    /// The lowest row the current piece reached, only going below it gives the delay back
+    lowest_row: Option<i32>,
+}
 
-#[derive(Resource)]
-pub struct PiecesQueue(VecDeque<PieceType>);
+impl LockDelay {
+    pub fn new(seconds: f32, max_resets: u32) -> Self {
+        Self {
+            timer: Timer::from_seconds(seconds, TimerMode::Once),
+            resets: 0,
+            max_resets,
+            lowest_row: None,
+        }
+    }
 
-impl PiecesQueue {
-    pub fn new() -> Self {
-        let mut result = Self(VecDeque::new());
-        result.generate();
-        result
+    /// Restart the timer and the resets when the piece gets below the lowest row it reached.
+    ///
+    /// Lifting the piece off the stack only pauses the timer, so it can't be used to stall.
+    pub fn track_row(&mut self, row: i32) {
+        if self.lowest_row.is_none_or(|lowest| row < lowest) {
+            self.lowest_row = Some(row);
+            self.resets = 0;
+            self.timer.reset();
+        }
+    }
+
+    /// Restart the timer after the player moved or rotated the piece, while there are resets left
+    pub fn reset(&mut self) {
+        if self.resets < self.max_resets {
+            self.resets += 1;
+            self.timer.reset();
+        }
+    }
+
+    /// Finish the timer so the piece locks as soon as it is grounded
+    pub fn lock_now(&mut self) {
+        let remaining = self.timer.remaining();
+        self.timer.tick(remaining);
     }
 
-    /// Generates a new queue of pieces
+    /// Prepare the delay for a new piece
+    pub fn clear(&mut self) {
+        self.resets = 0;
+        self.lowest_row = None;
+        self.timer.reset();
+    }
+}
+
+/// How the piece responds to the keys, chosen before the game starts
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct Handling {
+    /// Seconds a horizontal key must be held before the movement repeats (DAS)
+    pub shift_delay: f32,
+    /// Seconds between each repeated horizontal movement (ARR)
+    pub repeat_rate: f32,
+    /// How many times faster than gravity the soft drop is
+    pub soft_drop_factor: f32,
+}
+
+impl Handling {
+    /// Create the handling, replacing the values the timers can't use.
     ///
-    /// Only the first generated set of pieces is guaranteed to be unique.
-    /// Other generated sets may contain duplicates, due to extending the queue.
-    fn generate(&mut self) {
-        let mut rng = thread_rng();
-
-        let mut pieces = Vec::new();
-        loop {
-            match PIECES.get(rng.gen_range(0..7)) {
-                Some(piece) => {
-                    if pieces.contains(piece) {
-                        continue;
-                    }
-                    pieces.push(*piece);
-                }
-                None => {
-                    // Just ignore the error and try again logging the error
-                    error!("Failed to generate a piece");
-                }
-            }
-            if pieces.len() >= (CACHED_PIECES - self.0.len()) {
-                break;
+    /// The repeat rate and the soft drop can't be instant, they would move the piece without end.
+    pub fn new(shift_delay: f32, repeat_rate: f32, soft_drop_factor: f32) -> Self {
+        let valid = |value: f32, min: f32, max: f32, default: f32| {
+            if value.is_finite() {
+                value.clamp(min, max)
+            } else {
+                default
             }
+        };
+        Self {
+            shift_delay: valid(shift_delay, 0.0, 1.0, AUTO_SHIFT_DELAY),
+            repeat_rate: valid(repeat_rate, 0.001, 1.0, AUTO_REPEAT_RATE),
+            soft_drop_factor: valid(soft_drop_factor, 1.0, 100.0, SOFT_DROP_FACTOR),
+        }
+    }
+}
+
+impl Default for Handling {
+    fn default() -> Self {
+        Self::new(AUTO_SHIFT_DELAY, AUTO_REPEAT_RATE, SOFT_DROP_FACTOR)
+    }
+}
+
+/// State of the manual movement of the piece.
+///
+/// The horizontal keys use Delayed Auto Shift (DAS) and Auto Repeat Rate (ARR): the first press
+/// moves right away and, if the key is held, the movement repeats after a delay.
+#[derive(Resource)]
+pub struct ManualMove {
+    /// The horizontal direction being held, -1 for left, 1 for right and 0 for none
+    pub direction: i32,
+    /// Delay before the movement starts repeating (DAS)
+    pub shift_delay: Timer,
+    /// Time between the repeated movements (ARR)
+    pub repeat_rate: Timer,
+    /// How many times faster than gravity the soft drop is
+    pub soft_drop_factor: f32,
+    pub soft_drop: Timer,
+}
+
+impl ManualMove {
+    pub fn new(handling: Handling) -> Self {
+        let Handling {
+            shift_delay,
+            repeat_rate,
+            soft_drop_factor,
+        } = handling;
+        Self {
+            direction: 0,
+            shift_delay: Timer::from_seconds(shift_delay, TimerMode::Once),
+            repeat_rate: Timer::from_seconds(repeat_rate, TimerMode::Repeating),
+            soft_drop_factor,
+            soft_drop: Timer::from_seconds(1.0 / soft_drop_factor, TimerMode::Repeating),
+        }
+    }
+
+    /// Start moving in a new direction, restarting the delay
+    pub fn shift(&mut self, direction: i32) {
+        self.direction = direction;
+        self.shift_delay.reset();
+        self.repeat_rate.reset();
+    }
+}
+
+/// The optional rules, chosen before the game starts
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct Rules {
+    /// End the game when a piece locks with any block above the cup
+    pub partial_lock_out: bool,
+    /// Topping out ends the game, otherwise the bottom rows clear away to make room
+    pub top_out: bool,
+    /// The pieces fall on their own, otherwise they only move down with the soft drop
+    pub gravity: bool,
+    /// The garbage rows the board starts with
+    pub garbage_rows: usize,
+    /// The chance the hole of a garbage row moves to another column
+    pub hole_change: f64,
+    /// Garbage rows push up from the bottom, faster after every row
+    pub rising_garbage: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            partial_lock_out: false,
+            top_out: true,
+            gravity: true,
+            garbage_rows: 0,
+            hole_change: GARBAGE_HOLE_CHANGE,
+            rising_garbage: false,
+        }
+    }
+}
+
+/// The timer of the garbage that rises from the bottom
+#[derive(Resource)]
+pub struct RisingGarbage {
+    pub timer: Timer,
+    /// The shortest time between two rows
+    pub min_seconds: f32,
+    /// The fraction of the time of the last row that the next one takes
+    pub speed_up: f32,
+}
+
+impl RisingGarbage {
+    pub fn new(seconds: f32, min_seconds: f32, speed_up: f32) -> Self {
+        Self {
+            timer: Timer::from_seconds(seconds, TimerMode::Repeating),
+            min_seconds,
+            speed_up,
+        }
+    }
+
+    /// Make the next row rise sooner, down to the minimum time
+    pub fn speed_up(&mut self) {
+        let seconds = self.timer.duration().as_secs_f32() * self.speed_up;
+        self.timer
+            .set_duration(Duration::from_secs_f32(seconds.max(self.min_seconds)));
+    }
+}
+
+/// The board with the rules of the game, shared by all the systems
+#[derive(Resource)]
+pub struct Playfield(pub Board);
+
+/// Consecutive clears that earn bonus points
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Streak {
+    /// How many locks in a row cleared lines, not counting the first one
+    pub combo: Option<u32>,
+    /// The last clear was difficult, a Tetris or a T-spin that cleared lines
+    pub back_to_back: bool,
+}
+
+impl Streak {
+    /// Update the streak after a piece locks.
+    ///
+    /// Returns `true` if the clear gets the back-to-back bonus.
+    pub fn lock(&mut self, lines: u64, t_spin: TSpin) -> bool {
+        if lines == 0 {
+            // A spin without lines keeps the back-to-back, but any lock without lines breaks the combo
+            self.combo = None;
+            return false;
+        }
+        self.combo = Some(self.combo.map_or(0, |combo| combo + 1));
+        let difficult = lines == 4 || t_spin != TSpin::None;
+        let bonus = difficult && self.back_to_back;
+        self.back_to_back = difficult;
+        bonus
+    }
+}
+
+/// The piece kept aside by the player
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct HoldPiece {
+    pub piece: Option<PieceType>,
+    /// Only one hold is allowed until the piece locks
+    pub can_hold: bool,
+}
+
+impl HoldPiece {
+    pub fn new() -> Self {
+        Self {
+            piece: None,
+            can_hold: true,
         }
-        debug!("Generated pieces: {:?}", pieces);
+    }
+}
+
+/// The seed used to generate the pieces, so a game can be reproduced
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct Seed {
+    /// The seed chosen by the player, every game uses it when set
+    pub fixed: Option<u64>,
+    /// The seed of the current game
+    pub current: u64,
+}
+
+impl Seed {
+    pub fn new(fixed: Option<u64>) -> Self {
+        Self {
+            fixed,
+            current: fixed.unwrap_or_default(),
+        }
+    }
 
-        // Add the pieces to the queue
-        self.0.extend(pieces);
+    /// Pick the seed for a new game, a random one if the player didn't choose it
+    pub fn next_game(&mut self) -> u64 {
+        self.current = self.fixed.unwrap_or_else(|| thread_rng().gen());
+        self.current
     }
+}
+
+#[derive(Resource)]
+pub struct PiecesQueue {
+    pieces: VecDeque<PieceType>,
+    rng: ChaCha8Rng,
+    randomizer: Box<dyn Randomizer>,
+}
 
-    pub fn is_empty(&self) -> bool {
-        self.0.is_empty()
+impl PiecesQueue {
+    /// Create a queue that always gives the same pieces for the same seed and randomizer.
+    ///
+    /// ChaCha is used instead of the `StdRng` of `rand`, that can change between versions and
+    /// platforms, so a seed can be shared for races and bug reports.
+    pub fn new(seed: u64, kind: RandomizerKind) -> Self {
+        let mut result = Self {
+            pieces: VecDeque::new(),
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            randomizer: kind.build(),
+        };
+        result.generate();
+        result
+    }
+
+    /// Fills the queue with new pieces from the randomizer
+    fn generate(&mut self) {
+        while self.pieces.len() <= CACHED_PIECES {
+            let piece = self.randomizer.next(&mut self.rng);
+            self.pieces.push_back(piece);
+        }
     }
 
     /// Gets the next piece and keeps the queue filled
     pub fn next(&mut self) -> PieceType {
         // Always keep the queue filled
-        if self.0.len() <= CACHED_PIECES {
-            self.generate();
+        self.generate();
+        self.pieces.pop_front().unwrap()
+    }
+
+    /// The pieces that come after the current one, in order
+    pub fn upcoming(&self) -> Vec<PieceType> {
+        self.pieces.iter().take(CACHED_PIECES).copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::piece::components::PIECES;
+
+    #[test]
+    fn handling_replaces_invalid_values() {
+        let handling = Handling::new(f32::NAN, 0.0, 0.0);
+        assert_eq!(handling.shift_delay, AUTO_SHIFT_DELAY);
+        assert!(handling.repeat_rate > 0.0);
+        assert_eq!(handling.soft_drop_factor, 1.0);
+        // The timers of the manual movement can be built from it
+        ManualMove::new(handling);
+        assert_eq!(Handling::new(0.1, 0.02, 10.0).soft_drop_factor, 10.0);
+    }
+
+    #[test]
+    fn lifting_after_the_cap_doesnt_restore_the_timer() {
+        let step = Duration::from_secs_f32(0.2);
+        let mut delay = LockDelay::new(0.5, 2);
+        delay.track_row(5);
+        for _ in 0..2 {
+            delay.timer.tick(step);
+            delay.reset();
         }
-        self.0.pop_front().unwrap()
+        assert_eq!(delay.resets, 2);
+
+        // No resets left, moving or lifting the piece keeps the time that passed
+        delay.timer.tick(step);
+        delay.reset();
+        delay.track_row(6);
+        delay.track_row(5);
+        assert_eq!(delay.timer.elapsed(), step);
+
+        // Only a new lowest row gives the delay back
+        delay.track_row(4);
+        assert_eq!(delay.resets, 0);
+        assert_eq!(delay.timer.elapsed(), Duration::ZERO);
+    }
+
+    #[test]
+    fn streak_follows_the_combo_rules() {
+        let mut streak = Streak::default();
+        assert!(!streak.lock(1, TSpin::None));
+        assert_eq!(streak.combo, Some(0));
+        streak.lock(2, TSpin::None);
+        streak.lock(1, TSpin::None);
+        assert_eq!(streak.combo, Some(2));
+
+        // Any lock without lines breaks the combo, even a spin
+        streak.lock(0, TSpin::Full);
+        assert_eq!(streak.combo, None);
+        streak.lock(1, TSpin::None);
+        assert_eq!(streak.combo, Some(0));
+    }
+
+    #[test]
+    fn streak_follows_the_back_to_back_rules() {
+        let mut streak = Streak::default();
+        // The first difficult clear starts the back-to-back, the next one gets the bonus
+        assert!(!streak.lock(4, TSpin::None));
+        assert!(streak.back_to_back);
+        assert!(streak.lock(2, TSpin::Full));
+        assert!(streak.lock(1, TSpin::Mini));
+
+        // A spin without lines keeps it
+        assert!(!streak.lock(0, TSpin::Full));
+        assert!(streak.back_to_back);
+        assert!(streak.lock(4, TSpin::None));
+
+        // A simple clear breaks it
+        assert!(!streak.lock(3, TSpin::None));
+        assert!(!streak.back_to_back);
+        assert!(!streak.lock(4, TSpin::None));
     }
 
-    pub fn peek(&self) -> Option<&PieceType> {
-        self.0.front()
+    #[test]
+    fn every_bag_has_all_the_pieces() {
+        let mut queue = PiecesQueue::new(0, RandomizerKind::SevenBag);
+        for _ in 0..2000 {
+            let bag = (0..PIECES.len())
+                .map(|_| queue.next())
+                .collect::<BTreeSet<_>>();
+            assert_eq!(bag.len(), PIECES.len());
+        }
+    }
+
+    #[test]
+    fn same_piece_comes_back_within_13_pieces() {
+        let mut queue = PiecesQueue::new(0, RandomizerKind::SevenBag);
+        let mut last_seen = [None; 7];
+        for i in 0..14_000 {
+            let piece = queue.next();
+            if let Some(last) = last_seen[piece as usize] {
+                // At worst it is the first of a bag and the last of the next one
+                assert!(i - last <= 13, "{piece:?} took {} pieces", i - last);
+            }
+            last_seen[piece as usize] = Some(i);
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_pieces() {
+        let kinds = [
+            RandomizerKind::SevenBag,
+            RandomizerKind::FourteenBag,
+            RandomizerKind::PureRandom,
+            RandomizerKind::Nes,
+            RandomizerKind::Tgm,
+        ];
+        for kind in kinds {
+            for seed in [0, 1, 42, u64::MAX] {
+                let mut first = PiecesQueue::new(seed, kind);
+                let mut second = PiecesQueue::new(seed, kind);
+                for _ in 0..1000 {
+                    assert_eq!(first.next(), second.next());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn upcoming_pieces_come_next() {
+        let mut queue = PiecesQueue::new(0, RandomizerKind::SevenBag);
+        for _ in 0..1000 {
+            let upcoming = queue.upcoming();
+            assert_eq!(upcoming.len(), CACHED_PIECES);
+            for piece in upcoming {
+                assert_eq!(piece, queue.next());
+            }
+        }
     }
 }
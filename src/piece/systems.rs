@@ -1,292 +1,405 @@
-use std::collections::BTreeSet;
-
 use bevy::prelude::*;
 
 use crate::{
-    common::VISIBILITY_LIMIT_Y,
+    common::{
+        BOARD_BUFFER_ROWS, LOCK_DELAY, LOCK_DELAY_RESETS, SURVIVAL_MIN_RISE_SECONDS,
+        SURVIVAL_RISE_SECONDS, SURVIVAL_RISE_SPEED_UP,
+    },
+    grid::BoardSize,
     state::GameState,
-    stats::{NextPieceEvent, Score, ScoreEvent},
+    stats::{Level, NextPieceEvent, PerfectClearEvent, Score, ScoreEvent},
 };
 
 use super::{
-    components::{Block, Movable, Piece, PieceType},
-    resources::{ManualMoveTimer, MoveDownTimer, PiecesQueue},
+    board::{Board, TSpin},
+    components::{Block, GhostBlock, PieceType, Turn},
+    events::{GameOverEvent, PieceLockedEvent},
+    garbage::GarbageGenerator,
+    gravity::MAX_GRAVITY_SECONDS,
+    randomizer::RandomizerKind,
+    resources::{
+        Handling, HoldPiece, LockDelay, ManualMove, MoveDownTimer, PiecesQueue, Playfield,
+        RisingGarbage, Rules, Seed, Streak,
+    },
 };
 
-/// System to setup the pieces queue at the start of the game
-pub fn setup_game(mut commands: Commands) {
-    commands.insert_resource(PiecesQueue::new());
-    commands.insert_resource(MoveDownTimer(Timer::from_seconds(
-        1.0,
-        TimerMode::Repeating,
-    )));
-    commands.insert_resource(ManualMoveTimer(Timer::from_seconds(
-        0.05,
-        TimerMode::Repeating,
-    )));
+/// System to setup the board and the pieces queue at the start of the game
+pub fn setup_game(
+    mut commands: Commands,
+    mut seed: ResMut<Seed>,
+    randomizer: Res<RandomizerKind>,
+    size: Res<BoardSize>,
+    rules: Res<Rules>,
+    handling: Res<Handling>,
+) {
+    let game_seed = seed.next_game();
+    let mut board = Board::new(size.cols, size.rows, BOARD_BUFFER_ROWS)
+        .with_partial_lock_out(rules.partial_lock_out);
+    let mut garbage = GarbageGenerator::new(game_seed, rules.hole_change);
+    // Never more garbage than the cup can hold
+    for y in 0..rules.garbage_rows.min(size.rows - 1) {
+        garbage.spawn_row(&mut commands, &mut board, y as i32);
+    }
+    commands.insert_resource(Playfield(board));
+    commands.insert_resource(garbage);
+    if rules.rising_garbage {
+        commands.insert_resource(RisingGarbage::new(
+            SURVIVAL_RISE_SECONDS,
+            SURVIVAL_MIN_RISE_SECONDS,
+            SURVIVAL_RISE_SPEED_UP,
+        ));
+    }
+    commands.insert_resource(PiecesQueue::new(game_seed, *randomizer));
+    commands.insert_resource(HoldPiece::new());
+    commands.insert_resource(Streak::default());
+    let mut drop_timer = Timer::from_seconds(1.0, TimerMode::Repeating);
+    if !rules.gravity {
+        drop_timer.pause();
+    }
+    commands.insert_resource(MoveDownTimer(drop_timer));
+    commands.insert_resource(LockDelay::new(LOCK_DELAY, LOCK_DELAY_RESETS));
+    commands.insert_resource(ManualMove::new(*handling));
 }
 
 /// System to add a new piece to the game when
 /// the current one is gone or at the start of the game
 pub fn add_piece(
     mut commands: Commands,
-    query: Query<&PieceType>,
+    mut playfield: ResMut<Playfield>,
     mut pieces: ResMut<PiecesQueue>,
+    mut hold: ResMut<HoldPiece>,
     mut next_piece_event: EventWriter<NextPieceEvent>,
 ) {
-    if query.is_empty() {
-        pieces.next().build(&mut commands);
-        let Some(next) = pieces.peek() else {
-            return;
-        };
-        next_piece_event.send(NextPieceEvent(*next));
+    if playfield.0.active().is_none() {
+        spawn_piece(&mut commands, &mut playfield.0, pieces.next());
+        // The last piece locked, so the player can hold again
+        hold.can_hold = true;
+        next_piece_event.send(NextPieceEvent(pieces.upcoming()));
     }
 }
 
-/// System to control the visibility of the pieces
-pub fn visibility_control(mut query: Query<(&Block, &mut Visibility), With<PieceType>>) {
-    for (piece, mut visible) in query.iter_mut() {
-        if piece.y() >= VISIBILITY_LIMIT_Y {
-            *visible = Visibility::Hidden;
-        } else {
-            *visible = Visibility::Visible;
+/// System to swap the falling piece with the held one,
+/// or with the next piece of the queue when nothing is held yet
+#[allow(clippy::too_many_arguments)]
+pub fn hold_piece(
+    mut commands: Commands,
+    mut playfield: ResMut<Playfield>,
+    mut pieces: ResMut<PiecesQueue>,
+    mut hold: ResMut<HoldPiece>,
+    mut lock_delay: ResMut<LockDelay>,
+    q_moveable_blocks: Query<Entity, With<PieceType>>,
+    q_ghost_blocks: Query<Entity, With<GhostBlock>>,
+    mut next_piece_event: EventWriter<NextPieceEvent>,
+) {
+    if !hold.can_hold {
+        return;
+    }
+    let Some(current) = playfield.0.active().map(|piece| piece.piece_type()) else {
+        return;
+    };
+
+    let piece_type = match hold.piece.replace(current) {
+        Some(held) => held,
+        None => {
+            let next = pieces.next();
+            next_piece_event.send(NextPieceEvent(pieces.upcoming()));
+            next
         }
+    };
+    hold.can_hold = false;
+    lock_delay.clear();
+
+    // The held piece starts again from the top when it comes back
+    for entity in q_moveable_blocks.iter().chain(q_ghost_blocks.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_piece(&mut commands, &mut playfield.0, piece_type);
+}
+
+/// Helper function to place a new piece at the spawn position, with its blocks and ghost
+fn spawn_piece(commands: &mut Commands, board: &mut Board, piece_type: PieceType) {
+    board.spawn(piece_type);
+    if let Some(piece) = board.active() {
+        piece_type.build(commands, &piece.blocks());
+    }
+    if let Some(landing) = board.landing() {
+        piece_type.build_ghost(commands, &landing.blocks());
     }
 }
 
+/// System to move the piece down with gravity and with the player input
+#[allow(clippy::too_many_arguments)]
 pub fn move_piece(
     time: Res<Time>,
-    q_static_blocks: Query<&Block, Without<PieceType>>,
-    mut q_moveable_blocks: Query<(&mut Block, &mut Transform, &PieceType), With<PieceType>>,
+    mut playfield: ResMut<Playfield>,
+    mut q_moveable_blocks: Query<(&mut Block, &mut Transform), With<PieceType>>,
+    mut q_ghost_blocks: Query<&mut Transform, (With<GhostBlock>, Without<PieceType>)>,
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut manual_timer: ResMut<ManualMoveTimer>,
+    mut manual: ResMut<ManualMove>,
     mut auto_timer: ResMut<MoveDownTimer>,
+    mut lock_delay: ResMut<LockDelay>,
 ) {
-    let manual = manual_timer.0.tick(time.delta()).just_finished();
-    let auto = auto_timer.0.tick(time.delta()).just_finished();
+    let board = &mut playfield.0;
+    // Gravity can be faster than the frame rate, falling several rows in the same tick,
+    // and at 20G the piece falls straight to the stack
+    let rows = if auto_timer.0.paused() {
+        0
+    } else if auto_timer.0.duration().as_secs_f32() <= MAX_GRAVITY_SECONDS {
+        board.height() as u32
+    } else {
+        auto_timer.0.tick(time.delta()).times_finished_this_tick()
+    };
+    let mut move_down = false;
+    for _ in 0..rows {
+        if !board.move_down() {
+            break;
+        }
+        move_down = true;
+    }
 
-    // We only calculate collisions if we are moving the piece
-    if auto || manual {
-        // Update collisions we can detect but we need to keep the piece look
-        let blocks = q_moveable_blocks
-            .iter()
-            .map(|(b, _, _)| *b)
-            .collect::<Vec<_>>();
-        let mut moveable = valid_move(&blocks, &q_static_blocks);
+    // The most recently pressed direction wins, and when it is released
+    // we go back to the other one if it is still held
+    let left = keyboard_input.pressed(KeyCode::ArrowLeft);
+    let right = keyboard_input.pressed(KeyCode::ArrowRight);
+    let new_direction = if keyboard_input.just_pressed(KeyCode::ArrowLeft) {
+        Some(-1)
+    } else if keyboard_input.just_pressed(KeyCode::ArrowRight) {
+        Some(1)
+    } else if (manual.direction == -1 && !left) || (manual.direction == 1 && !right) {
+        Some(right as i32 - left as i32)
+    } else {
+        None
+    };
 
-        // If is auto move, we only move down and ignore the rest
-        let mut move_down = false;
-        if auto && moveable.can_move_down() {
-            move_down = true;
-            for (mut block, mut transform, _) in q_moveable_blocks.iter_mut() {
-                block.move_down();
-                transform.translation = block.as_board_translation();
-            }
-            // Update collisions
-            let blocks = q_moveable_blocks
-                .iter()
-                .map(|(b, _, _)| *b)
-                .collect::<Vec<_>>();
-            moveable = valid_move(&blocks, &q_static_blocks);
+    let shift = match new_direction {
+        // A new direction moves right away, then waits for the delay to repeat
+        Some(direction) => {
+            manual.shift(direction);
+            direction
+        }
+        None if manual.direction != 0 && manual.shift_delay.tick(time.delta()).finished() => {
+            // The first repeat happens as soon as the delay is over
+            let repeats = if manual.shift_delay.just_finished() {
+                1
+            } else {
+                manual
+                    .repeat_rate
+                    .tick(time.delta())
+                    .times_finished_this_tick() as i32
+            };
+            manual.direction * repeats
         }
+        None => 0,
+    };
+    let mut moved = false;
+    for _ in 0..shift.abs() {
+        if !board.try_move(shift.signum(), 0) {
+            break;
+        }
+        moved = true;
+    }
 
-        for (mut block, mut transform, _) in q_moveable_blocks.iter_mut() {
-            let mut moved = false;
-            if keyboard_input.pressed(KeyCode::ArrowLeft) && moveable.can_move_left() {
-                block.move_left();
-                moved = true;
-            } else if keyboard_input.pressed(KeyCode::ArrowRight) && moveable.can_move_right() {
-                block.move_right();
-                moved = true;
-            } else if keyboard_input.pressed(KeyCode::ArrowDown)
-                && moveable.can_move_down()
-                && !move_down
-            {
-                block.move_down();
-                moved = true;
-            }
+    // The soft drop falls faster than gravity, and the piece doesn't move down twice in the same frame
+    let soft_drop_duration = auto_timer.0.duration().div_f32(manual.soft_drop_factor);
+    manual.soft_drop.set_duration(soft_drop_duration);
+    if keyboard_input.just_pressed(KeyCode::ArrowDown) {
+        manual.soft_drop.reset();
+    }
+    let soft_drop = keyboard_input.pressed(KeyCode::ArrowDown)
+        && (keyboard_input.just_pressed(KeyCode::ArrowDown)
+            || manual.soft_drop.tick(time.delta()).just_finished())
+        && !move_down
+        && board.move_down();
 
-            if moved {
-                transform.translation = block.as_board_translation();
-            }
-        }
+    if move_down || moved || soft_drop {
+        sync_active_blocks(board, &mut q_moveable_blocks, &mut q_ghost_blocks);
+    }
+    // Moving a piece that rests on the stack gives it more time to slide
+    if moved && board.is_grounded() {
+        lock_delay.reset();
     }
 }
 
 /// System to rotate the piece
 pub fn rotate_piece(
-    q_static_blocks: Query<&Block, Without<PieceType>>,
-    mut q_moveable_blocks: Query<(&mut Block, &mut Transform, &PieceType), With<PieceType>>,
+    mut playfield: ResMut<Playfield>,
+    mut q_moveable_blocks: Query<(&mut Block, &mut Transform), With<PieceType>>,
+    mut q_ghost_blocks: Query<&mut Transform, (With<GhostBlock>, Without<PieceType>)>,
     keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut lock_delay: ResMut<LockDelay>,
 ) {
-    if keyboard_input.just_released(KeyCode::ArrowUp) {
-        // Update collisions we can detect but we need to keep the piece look
-        let blocks = q_moveable_blocks
-            .iter()
-            .map(|(b, _, _)| *b)
-            .collect::<Vec<_>>();
-        let Some(piece_type) = q_moveable_blocks.iter().next().map(|(_, _, p)| p) else {
-            return;
-        };
-        let piece = Piece::from_array(&blocks, *piece_type);
-        let rotate_blocks = piece.rotate_blocks();
-        let can_rotate = valid_rotation(&rotate_blocks, &q_static_blocks).can_rotate();
+    // Each turn is a separate action, triggered when the key is pressed
+    let turn = if keyboard_input.any_just_pressed([KeyCode::ArrowUp, KeyCode::KeyX]) {
+        Turn::Clockwise
+    } else if keyboard_input.any_just_pressed([KeyCode::KeyZ, KeyCode::ControlLeft]) {
+        Turn::CounterClockwise
+    } else if keyboard_input.just_pressed(KeyCode::KeyA) {
+        Turn::Half
+    } else {
+        return;
+    };
 
-        for (mut block, mut transform, _) in q_moveable_blocks.iter_mut() {
-            if can_rotate {
-                // Use our helper to do the rotation.
-                piece.rotate_block(&mut block);
-                transform.translation = block.as_board_translation();
-            }
+    if playfield.0.rotate(turn) {
+        sync_active_blocks(&playfield.0, &mut q_moveable_blocks, &mut q_ghost_blocks);
+        if playfield.0.is_grounded() {
+            lock_delay.reset();
         }
     }
 }
 
-/// Helper function to check if the piece can move.
-fn valid_move(blocks: &[Block], q_static_blocks: &Query<&Block, Without<PieceType>>) -> Movable {
-    let mut moveable = Movable::new();
-    for block in blocks.iter() {
-        if block.y() == 0
-            || q_static_blocks
-                .iter()
-                .any(|b| b.y() == block.y() - 1 && b.x() == block.x())
-        {
-            moveable.down = false;
-        }
-        if block.x() == 0
-            || q_static_blocks
-                .iter()
-                .any(|b| b.x() == block.x() - 1 && b.y() == block.y())
-        {
-            moveable.left = false;
-        }
-        if block.x() == 9
-            || q_static_blocks
-                .iter()
-                .any(|b| b.x() == block.x() + 1 && b.y() == block.y())
-        {
-            moveable.right = false;
-        }
+/// System to drop the piece to the lowest valid row.
+///
+/// The lock delay is skipped, so the piece is locked by the collisions check in the same frame.
+pub fn hard_drop(
+    mut playfield: ResMut<Playfield>,
+    mut q_moveable_blocks: Query<(&mut Block, &mut Transform), With<PieceType>>,
+    mut q_ghost_blocks: Query<&mut Transform, (With<GhostBlock>, Without<PieceType>)>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut lock_delay: ResMut<LockDelay>,
+    mut score_event: EventWriter<ScoreEvent>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Space) {
+        return;
+    }
+    let rows = playfield.0.hard_drop();
+    if let Some(piece) = playfield.0.active() {
+        lock_delay.track_row(piece.bottom());
+    }
+    lock_delay.lock_now();
+    if rows > 0 {
+        sync_active_blocks(&playfield.0, &mut q_moveable_blocks, &mut q_ghost_blocks);
+        // Each row dropped is worth 2 points
+        score_event.send(ScoreEvent {
+            score: Score {
+                value: rows * 2,
+                lines: 0,
+            },
+            ..default()
+        });
     }
-    moveable
 }
 
-fn valid_rotation(
-    blocks: &[Block],
-    q_static_blocks: &Query<&Block, Without<PieceType>>,
-) -> Movable {
-    let mut moveable = Movable::new();
-    for block in blocks.iter() {
-        if block.y() < 0
-            || q_static_blocks
-                .iter()
-                .any(|b| b.y() == block.y() - 1 && b.x() == block.x())
-        {
-            moveable.down = false;
-        }
-        if block.x() < 0
-            || q_static_blocks
-                .iter()
-                .any(|b| b.x() == block.x() - 1 && b.y() == block.y())
-        {
-            moveable.left = false;
-        }
-        if block.x() > 9
-            || q_static_blocks
-                .iter()
-                .any(|b| b.x() == block.x() + 1 && b.y() == block.y())
-        {
-            moveable.right = false;
-        }
+/// Helper function to mirror the active piece of the board into the falling blocks and its ghost.
+///
+/// All the blocks of a piece look the same, so the order they are updated doesn't matter.
+fn sync_active_blocks(
+    board: &Board,
+    q_moveable_blocks: &mut Query<(&mut Block, &mut Transform), With<PieceType>>,
+    q_ghost_blocks: &mut Query<&mut Transform, (With<GhostBlock>, Without<PieceType>)>,
+) {
+    let (Some(piece), Some(landing)) = (board.active(), board.landing()) else {
+        return;
+    };
+    for ((mut block, mut transform), target) in q_moveable_blocks.iter_mut().zip(piece.blocks()) {
+        *block = target;
+        transform.translation = block.as_board_translation();
+    }
+    for (mut transform, target) in q_ghost_blocks.iter_mut().zip(landing.blocks()) {
+        transform.translation = target.as_ghost_translation();
     }
-    moveable
 }
 
 /// System to check if the piece has collided with the bottom or another piece
-/// and remove the PieceType component to make it static.
+/// and, once the lock delay is over, remove the PieceType component to make it static.
 pub fn collisions_check(
     mut commands: Commands,
-    q_blocks: Query<&Block, Without<PieceType>>,
-    mut query: Query<(Entity, &Block), With<PieceType>>,
+    time: Res<Time>,
+    mut playfield: ResMut<Playfield>,
+    mut lock_delay: ResMut<LockDelay>,
+    query: Query<Entity, With<PieceType>>,
+    q_ghost_blocks: Query<Entity, With<GhostBlock>>,
+    mut locked_event: EventWriter<PieceLockedEvent>,
 ) {
-    let mut stop = false;
-    for (_, block) in query
-        .iter_mut()
-        .sort_by::<(Entity, &Block)>(|a, b| a.1.y().partial_cmp(&b.1.y()).unwrap())
-    {
-        // Check if the block can move down
-        if block.y() == 0
-            || q_blocks
-                .iter()
-                .any(|b| b.y() == block.y() - 1 && b.x() == block.x())
-        {
-            stop = true;
-            break;
-        }
+    let Some(piece) = playfield.0.active() else {
+        return;
+    };
+    lock_delay.track_row(piece.bottom());
+    if !playfield.0.is_grounded() {
+        // The delay only runs while the piece rests on the stack
+        return;
     }
 
-    if stop {
-        for (entity, _) in query.iter() {
+    if lock_delay.timer.tick(time.delta()).finished() {
+        // The spin depends on the last action, so it is checked before the piece is gone
+        let t_spin = playfield.0.t_spin();
+        playfield.0.lock();
+        locked_event.send(PieceLockedEvent { t_spin });
+        lock_delay.clear();
+        for entity in query.iter() {
             commands.entity(entity).remove::<PieceType>();
         }
+        // The ghost is not needed once the piece is static
+        for entity in q_ghost_blocks.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
     }
 }
 
-/// System to remove the lines that are full
+/// System to remove the lines that are full, after a piece locks
+#[allow(clippy::too_many_arguments)]
 pub fn remove_lines(
     mut commands: Commands,
+    mut playfield: ResMut<Playfield>,
+    mut streak: ResMut<Streak>,
+    level: Res<Level>,
     mut q_blocks: Query<(Entity, &mut Block, &mut Transform), Without<PieceType>>,
+    mut locked_event: EventReader<PieceLockedEvent>,
     mut score_event: EventWriter<ScoreEvent>,
+    mut perfect_clear_event: EventWriter<PerfectClearEvent>,
 ) {
-    let mut lines = [0; 20];
-    for (_, block, _) in q_blocks.iter() {
-        // Ignore blocks that are out of the board
-        if block.y() < 0 || block.y() >= 20 {
-            continue;
-        }
-        lines[block.y() as usize] += 1;
-    }
+    // Only one piece can lock per frame
+    let Some(&PieceLockedEvent { t_spin }) = locked_event.read().last() else {
+        return;
+    };
 
-    // We use a BTreeSet to keep the lines removed sorted and unique
-    let mut removed_lines = BTreeSet::new();
-    for (entity, block, _) in q_blocks.iter() {
-        // Ignore blocks that are out of the board
-        if block.y() < 0 || block.y() >= 20 {
-            continue;
-        }
-        if lines[block.y() as usize] == 10 {
-            removed_lines.insert(block.y());
-            commands.entity(entity).despawn_recursive();
-        }
+    // The removed lines are sorted from the bottom to the top
+    let removed_lines = playfield.0.clear_lines();
+    let lines = removed_lines.len() as u64;
+    let back_to_back = streak.lock(lines, t_spin);
+    // Guideline bonuses: 1.5 times for a back-to-back clear and 50 points per combo,
+    // all of them multiplied by the level
+    let mut score = clear_points(lines, t_spin);
+    if back_to_back {
+        score = score * 3 / 2;
     }
-
-    if removed_lines.is_empty() {
+    score += 50 * streak.combo.unwrap_or_default() as u64;
+    if lines > 0 && playfield.0.is_empty() {
+        score += perfect_clear_points(lines, back_to_back);
+        perfect_clear_event.send(PerfectClearEvent { lines });
+    }
+    score *= level.value;
+    if score == 0 {
         return;
     }
 
-    let lines = removed_lines.len() as u64;
-    let score = match lines {
-        1 => 40,
-        2 => 100,
-        3 => 300,
-        4 => 1200,
-        _ => 0,
-    };
+    score_event.send(ScoreEvent {
+        score: Score {
+            value: score,
+            lines,
+        },
+        t_spin,
+        back_to_back,
+    });
 
-    score_event.send(ScoreEvent(Score {
-        value: score,
-        lines,
-    }));
+    remove_static_rows(&mut commands, &mut q_blocks, &removed_lines);
+}
 
-    // Move blocks above the removed lines down
-    for (_, mut block, mut transform) in q_blocks.iter_mut() {
+/// Helper function to remove the blocks of the removed rows and move the blocks above them down
+fn remove_static_rows(
+    commands: &mut Commands,
+    q_blocks: &mut Query<(Entity, &mut Block, &mut Transform), Without<PieceType>>,
+    removed_rows: &[i32],
+) {
+    for (entity, mut block, mut transform) in q_blocks.iter_mut() {
         let y = block.y();
-        let mut offset = 0;
-        for &removed_y in &removed_lines {
-            if y > removed_y {
-                offset += 1;
-            }
+        if removed_rows.contains(&y) {
+            commands.entity(entity).despawn_recursive();
+            continue;
         }
+        let offset = removed_rows
+            .iter()
+            .filter(|&&removed_y| y > removed_y)
+            .count() as i32;
         if offset > 0 {
             block.shift_y(-offset);
             transform.translation = block.as_board_translation();
@@ -294,21 +407,125 @@ pub fn remove_lines(
     }
 }
 
+/// Points for the lines cleared by a piece.
+///
+/// T-spins follow the guideline values and score even when they clear no lines.
+fn clear_points(lines: u64, t_spin: TSpin) -> u64 {
+    match (t_spin, lines) {
+        (TSpin::None, 1) => 40,
+        (TSpin::None, 2) => 100,
+        (TSpin::None, 3) => 300,
+        (TSpin::None, 4) => 1200,
+        (TSpin::Mini, 0) => 100,
+        (TSpin::Mini, 1) => 200,
+        (TSpin::Mini, 2) => 400,
+        (TSpin::Full, 0) => 400,
+        (TSpin::Full, 1) => 800,
+        (TSpin::Full, 2) => 1200,
+        (TSpin::Full, 3) => 1600,
+        _ => 0,
+    }
+}
+
+/// Bonus points for a clear that leaves the board empty, on top of the points of the lines
+fn perfect_clear_points(lines: u64, back_to_back: bool) -> u64 {
+    match lines {
+        1 => 800,
+        2 => 1200,
+        3 => 1800,
+        _ if back_to_back => 3200,
+        _ => 2000,
+    }
+}
+
+/// System to push a garbage row up from the bottom every time the rising garbage timer finishes.
+///
+/// The static blocks move up to make room, the reverse of what `remove_lines` does.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn rise_garbage(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut rising: ResMut<RisingGarbage>,
+    mut playfield: ResMut<Playfield>,
+    mut garbage: ResMut<GarbageGenerator>,
+    mut q_blocks: Query<
+        (Entity, &mut Block, &mut Transform),
+        (Without<PieceType>, Without<GhostBlock>),
+    >,
+    mut q_moveable_blocks: Query<(&mut Block, &mut Transform), With<PieceType>>,
+    mut q_ghost_blocks: Query<&mut Transform, (With<GhostBlock>, Without<PieceType>)>,
+) {
+    if !rising.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    rising.speed_up();
+
+    let board = &mut playfield.0;
+    board.insert_rows_below(1);
+    for (entity, mut block, mut transform) in q_blocks.iter_mut() {
+        block.shift_y(1);
+        if block.y() >= board.total_height() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        transform.translation = block.as_board_translation();
+    }
+    garbage.spawn_row(&mut commands, board, 0);
+    sync_active_blocks(board, &mut q_moveable_blocks, &mut q_ghost_blocks);
+}
+
+/// System to handle a top out reported by the board.
+///
+/// The game ends, unless the rules turn the top outs off. Then the bottom half of the cup
+/// clears away so the game can go on.
 pub fn game_over_check(
-    q_blocks: Query<&Block, Without<PieceType>>,
+    mut commands: Commands,
+    mut playfield: ResMut<Playfield>,
+    rules: Res<Rules>,
+    mut q_blocks: Query<(Entity, &mut Block, &mut Transform), Without<PieceType>>,
+    q_ghost_blocks: Query<Entity, With<GhostBlock>>,
     mut state: ResMut<NextState<GameState>>,
+    mut game_over_event: EventWriter<GameOverEvent>,
 ) {
-    if q_blocks.iter().any(|b| b.y() >= VISIBILITY_LIMIT_Y) {
+    let Some(top_out) = playfield.0.top_out() else {
+        return;
+    };
+    if rules.top_out {
+        game_over_event.send(GameOverEvent(top_out));
         state.set(GameState::GameOver);
+        return;
+    }
+
+    let board = &mut playfield.0;
+    let removed_rows = board.clear_bottom_rows(board.height() / 2);
+    remove_static_rows(&mut commands, &mut q_blocks, &removed_rows);
+    // A piece that blocked out stays where it spawned, but it lands lower now
+    for entity in q_ghost_blocks.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if let (Some(piece), Some(landing)) = (board.active(), board.landing()) {
+        piece
+            .piece_type()
+            .build_ghost(&mut commands, &landing.blocks());
     }
 }
 
 /// System to clear the pieces from the board
-pub fn clear_pieces(mut commands: Commands, query: Query<Entity, With<Block>>) {
-    for entity in query.iter() {
+pub fn clear_pieces(
+    mut commands: Commands,
+    query: Query<Entity, With<Block>>,
+    q_ghost_blocks: Query<Entity, With<GhostBlock>>,
+) {
+    for entity in query.iter().chain(q_ghost_blocks.iter()) {
         commands.entity(entity).despawn_recursive();
     }
+    commands.remove_resource::<Playfield>();
     commands.remove_resource::<PiecesQueue>();
+    commands.remove_resource::<GarbageGenerator>();
+    commands.remove_resource::<RisingGarbage>();
+    commands.remove_resource::<HoldPiece>();
+    commands.remove_resource::<Streak>();
     commands.remove_resource::<MoveDownTimer>();
-    commands.remove_resource::<ManualMoveTimer>();
+    commands.remove_resource::<LockDelay>();
+    commands.remove_resource::<ManualMove>();
 }
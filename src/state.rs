@@ -14,4 +14,6 @@ pub enum GameState {
     Play,
     Pause,
     GameOver,
+    /// The goal of the mode was reached
+    Results,
 }
@@ -1,12 +1,16 @@
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
 
-use bevy::prelude::*;
+use bevy::{prelude::*, time::Stopwatch};
 use sickle_ui::prelude::*;
 
 use crate::{
-    common::BLOCK_SIZE,
-    piece::{select_piece, MoveDownTimer, PieceType, TetrisSet},
-    state::AppState,
+    common::{BLOCK_SIZE, LEADERBOARD_SIZE, LINES_PER_LEVEL, NEXT_PREVIEW_MAX},
+    mode::{GameMode, ModeSettings},
+    piece::{
+        select_piece, GravityCurve, HoldPiece, MoveDownTimer, PieceLockedEvent, PieceType, Streak,
+        TSpin, TetrisSet,
+    },
+    state::{AppState, GameState},
 };
 
 #[derive(Resource, Clone, Copy, Default, Debug, PartialEq, Eq)]
@@ -18,17 +22,151 @@ pub struct Score {
 #[derive(Debug, Clone, Copy, Resource)]
 pub struct HighScore(pub Score);
 
+/// The fastest time to reach the goal of each mode
+#[derive(Debug, Clone, Default, Resource)]
+pub struct BestTimes(HashMap<GameMode, Duration>);
+
+impl BestTimes {
+    pub fn get(&self, mode: GameMode) -> Option<Duration> {
+        self.0.get(&mode).copied()
+    }
+
+    /// Keep the time if it is the best of the mode, returning `true` if it is a new record
+    pub fn record(&mut self, mode: GameMode, time: Duration) -> bool {
+        let best = self.0.entry(mode).or_insert(time);
+        if time <= *best {
+            *best = time;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// The best scores of each timed mode, from the highest, kept apart from the high score
+#[derive(Debug, Clone, Default, Resource)]
+pub struct Leaderboards(HashMap<GameMode, Vec<u64>>);
+
+impl Leaderboards {
+    pub fn get(&self, mode: GameMode) -> &[u64] {
+        self.0.get(&mode).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Add a score to the leaderboard of the mode, returning its rank if it made it
+    pub fn submit(&mut self, mode: GameMode, score: u64) -> Option<usize> {
+        let scores = self.0.entry(mode).or_default();
+        let rank = scores
+            .iter()
+            .position(|&other| score > other)
+            .unwrap_or(scores.len());
+        if rank >= LEADERBOARD_SIZE {
+            return None;
+        }
+        scores.insert(rank, score);
+        scores.truncate(LEADERBOARD_SIZE);
+        Some(rank)
+    }
+}
+
+/// The time played, the pieces placed and the result of the current game
+#[derive(Debug, Clone, Default, Resource)]
+pub struct Session {
+    pub time: Stopwatch,
+    pub pieces: u64,
+    /// The place of the game in the leaderboard of the mode, once it is submitted
+    pub rank: Option<usize>,
+}
+
+impl Session {
+    /// Pieces per second
+    pub fn pps(&self) -> f32 {
+        let seconds = self.time.elapsed_secs();
+        if seconds > 0.0 {
+            self.pieces as f32 / seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+/// The level of the game, it goes up every 10 lines and makes the clears worth more
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct Level {
+    /// The level chosen to start the games at
+    pub start: u64,
+    pub value: u64,
+}
+
+impl Level {
+    pub fn new(start: u64) -> Self {
+        let start = start.max(1);
+        Self {
+            start,
+            value: start,
+        }
+    }
+
+    /// Update the level with the lines cleared since the start of the game
+    pub fn advance(&mut self, lines: u64) {
+        self.value = self.start + lines / LINES_PER_LEVEL;
+    }
+}
+
+/// How many of the upcoming pieces are shown, from 1 to 6
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct NextPreview(usize);
+
+impl NextPreview {
+    pub fn new(count: usize) -> Self {
+        Self(count.clamp(1, NEXT_PREVIEW_MAX))
+    }
+}
+
+/// The size of the upcoming pieces after the first one
+const NEXT_PIECE_SCALE: f32 = 0.6;
+
 #[derive(Component)]
 struct NextPieceTag;
 
 #[derive(Component)]
 struct NextPieceLabel;
 
+#[derive(Component)]
+struct HoldPieceTag;
+
+#[derive(Component)]
+struct HoldPieceLabel;
+
+#[derive(Debug, Clone, Copy, Default, Event)]
+pub struct ScoreEvent {
+    pub score: Score,
+    /// The spin of the piece that scored, if the points come from a lock
+    pub t_spin: TSpin,
+    /// The clear got the back-to-back bonus
+    pub back_to_back: bool,
+}
+
+/// Sent when a clear leaves the board without any block
 #[derive(Debug, Clone, Copy, Event)]
-pub struct ScoreEvent(pub Score);
+pub struct PerfectClearEvent {
+    pub lines: u64,
+}
 
+/// Sent with the new level when the player goes up a level
 #[derive(Debug, Clone, Copy, Event)]
-pub struct NextPieceEvent(pub PieceType);
+pub struct LevelUpEvent(pub u64);
+
+/// A message shown over the board for a while
+#[derive(Component)]
+struct Callout(Timer);
+
+/// Sent with the upcoming pieces, in order, every time the queue changes
+#[derive(Debug, Clone, Event)]
+pub struct NextPieceEvent(pub Vec<PieceType>);
+
+/// The systems that update the stats of the game, after the pieces are checked
+#[derive(SystemSet, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct StatsSet;
 
 pub struct StatsPlugin;
 
@@ -36,23 +174,35 @@ impl Plugin for StatsPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(HighScore(Score::default()))
             .insert_resource(Score::default())
+            .init_resource::<BestTimes>()
+            .init_resource::<Leaderboards>()
+            .init_resource::<Session>()
             .add_event::<ScoreEvent>()
             .add_event::<NextPieceEvent>()
-            .add_systems(Startup, (setup_score_ui, setup_next_piece_ui))
+            .add_event::<PerfectClearEvent>()
+            .add_event::<LevelUpEvent>()
+            .add_systems(Startup, (setup_score_ui, setup_hold_piece_ui))
+            .add_systems(OnEnter(AppState::GameState), reset_stats)
+            .add_systems(
+                OnTransition {
+                    entered: GameState::Play,
+                    exited: GameState::GameOver,
+                },
+                reset_stats,
+            )
             .add_systems(
-                OnEnter(AppState::GameState),
-                |mut commands: Commands,
-                 mut score_event: EventWriter<ScoreEvent>,
-                 query: Query<Entity, With<NextPieceTag>>| {
-                    // Reset the score when entering the game state
-                    commands.insert_resource(Score::default());
-                    // Send a score event to update the UI
-                    score_event.send(ScoreEvent(Score::default()));
-                    // Despawn the next piece
-                    for entity in query.iter() {
-                        commands.entity(entity).despawn_recursive();
-                    }
+                OnTransition {
+                    entered: GameState::Play,
+                    exited: GameState::Results,
                 },
+                reset_stats,
+            )
+            .add_systems(
+                Update,
+                update_session
+                    .in_set(StatsSet)
+                    .after(TetrisSet::Collision)
+                    .run_if(in_state(GameState::Play)),
             )
             .add_systems(
                 Update,
@@ -60,9 +210,36 @@ impl Plugin for StatsPlugin {
                     .after(TetrisSet::Spawn)
                     .run_if(on_event::<NextPieceEvent>().and_then(in_state(AppState::GameState))),
             )
+            .add_systems(
+                Update,
+                update_hold_piece.after(TetrisSet::Movement).run_if(
+                    resource_exists_and_changed::<HoldPiece>
+                        .and_then(in_state(AppState::GameState)),
+                ),
+            )
+            .add_systems(
+                Update,
+                update_streak.after(TetrisSet::Collision).run_if(
+                    resource_exists_and_changed::<Streak>.and_then(in_state(AppState::GameState)),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    show_perfect_clear
+                        .after(TetrisSet::Collision)
+                        .run_if(on_event::<PerfectClearEvent>()),
+                    show_level_up
+                        .after(StatsSet)
+                        .run_if(on_event::<LevelUpEvent>()),
+                    update_callouts,
+                )
+                    .run_if(in_state(GameState::Play)),
+            )
             .add_systems(
                 Update,
                 update_stats
+                    .in_set(StatsSet)
                     .after(TetrisSet::Collision)
                     .run_if(on_event::<ScoreEvent>().and_then(in_state(AppState::GameState))),
             );
@@ -72,11 +249,50 @@ impl Plugin for StatsPlugin {
 #[derive(Component)]
 enum ScoreText {
     Score,
+    /// The title of the best result of the mode, the high score or the best time
+    BestTitle,
     HighScore,
     Lines,
+    Level,
+    /// The name of the last clear or spin, like "T-SPIN DOUBLE"
+    Clear,
+}
+
+#[derive(Component)]
+enum StreakText {
+    Combo,
+    BackToBack,
 }
 
-pub fn setup_score_ui(mut commands: Commands, highscore: Res<HighScore>) {
+#[derive(Component)]
+struct ClockText;
+
+/// Reset the stats at the start of every game
+fn reset_stats(
+    mut commands: Commands,
+    mut level: ResMut<Level>,
+    mut score_event: EventWriter<ScoreEvent>,
+    mut q_score: Query<(&mut Text, &ScoreText)>,
+    query: Query<Entity, With<NextPieceTag>>,
+) {
+    commands.insert_resource(Score::default());
+    commands.insert_resource(Session::default());
+    *level = Level::new(level.start);
+    // Send a score event to update the UI
+    score_event.send(ScoreEvent::default());
+    // The last clear belongs to the previous game
+    for (mut text, score_text) in q_score.iter_mut() {
+        if let ScoreText::Clear = score_text {
+            text.sections[0].value.clear();
+        }
+    }
+    // Despawn the next piece
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn setup_score_ui(mut commands: Commands, highscore: Res<HighScore>, level: Res<Level>) {
     commands
         .ui_builder(UiRoot)
         .container(
@@ -103,7 +319,7 @@ pub fn setup_score_ui(mut commands: Commands, highscore: Res<HighScore>) {
                         .justify_content(JustifyContent::Start);
                     column.row(|row| {
                         row.style()
-                            .padding(UiRect::top(Val::Px(50.0)))
+                            .padding(UiRect::top(Val::Px(30.0)))
                             .justify_content(JustifyContent::Center);
                         row.label(LabelConfig::from("Score"))
                             .style()
@@ -127,7 +343,9 @@ pub fn setup_score_ui(mut commands: Commands, highscore: Res<HighScore>) {
                         row.label(LabelConfig::from("High Score"))
                             .style()
                             .font_size(24.0)
-                            .align_self(AlignSelf::Center);
+                            .align_self(AlignSelf::Center)
+                            .entity_commands()
+                            .insert(ScoreText::BestTitle);
                     });
                     column.row(|row| {
                         row.style().justify_content(JustifyContent::Center);
@@ -157,13 +375,55 @@ pub fn setup_score_ui(mut commands: Commands, highscore: Res<HighScore>) {
                             .entity_commands()
                             .insert(ScoreText::Lines);
                     });
+
+                    column.row(|row| {
+                        row.style()
+                            .padding(UiRect::top(Val::Px(20.0)))
+                            .justify_content(JustifyContent::Center);
+                        row.label(LabelConfig::from("Level"))
+                            .style()
+                            .font_size(24.0)
+                            .align_self(AlignSelf::Center);
+                    });
+                    column.row(|row| {
+                        row.style().justify_content(JustifyContent::Center);
+                        row.label(LabelConfig::from(level.start.to_string()))
+                            .style()
+                            .font_size(24.0)
+                            .align_self(AlignSelf::Center)
+                            .entity_commands()
+                            .insert(ScoreText::Level);
+                    });
+                    column.row(|row| {
+                        row.style()
+                            .padding(UiRect::top(Val::Px(10.0)))
+                            .justify_content(JustifyContent::Center);
+                        row.label(LabelConfig::from(""))
+                            .style()
+                            .font_size(20.0)
+                            .align_self(AlignSelf::Center)
+                            .entity_commands()
+                            .insert(ScoreText::Clear);
+                    });
+
+                    column.row(|row| {
+                        row.style()
+                            .padding(UiRect::top(Val::Px(10.0)))
+                            .justify_content(JustifyContent::Center);
+                        row.label(LabelConfig::from("Next"))
+                            .style()
+                            .font_size(24.0)
+                            .align_self(AlignSelf::Center)
+                            .entity_commands()
+                            .insert(NextPieceLabel);
+                    });
                 });
             },
         )
         .insert(Name::new("ScoreRoot"));
 }
 
-pub fn setup_next_piece_ui(mut commands: Commands) {
+pub fn setup_hold_piece_ui(mut commands: Commands) {
     commands
         .ui_builder(UiRoot)
         .container(
@@ -192,44 +452,128 @@ pub fn setup_next_piece_ui(mut commands: Commands) {
                         row.style()
                             .padding(UiRect::top(Val::Px(50.0)))
                             .justify_content(JustifyContent::Center);
-                        row.label(LabelConfig::from("Next Piece"))
+                        row.label(LabelConfig::from("Hold"))
                             .style()
                             .font_size(24.0)
                             .align_self(AlignSelf::Center)
                             .entity_commands()
-                            .insert(NextPieceLabel);
+                            .insert(HoldPieceLabel);
+                    });
+
+                    // Leave room for the held piece
+                    column.row(|row| {
+                        row.style()
+                            .padding(UiRect::top(Val::Px(120.0)))
+                            .justify_content(JustifyContent::Center);
+                        row.label(LabelConfig::from("Combo"))
+                            .style()
+                            .font_size(24.0)
+                            .align_self(AlignSelf::Center);
+                    });
+                    column.row(|row| {
+                        row.style().justify_content(JustifyContent::Center);
+                        row.label(LabelConfig::from("0"))
+                            .style()
+                            .font_size(24.0)
+                            .align_self(AlignSelf::Center)
+                            .entity_commands()
+                            .insert(StreakText::Combo);
+                    });
+                    column.row(|row| {
+                        row.style()
+                            .padding(UiRect::top(Val::Px(10.0)))
+                            .justify_content(JustifyContent::Center);
+                        row.label(LabelConfig::from(""))
+                            .style()
+                            .font_size(20.0)
+                            .align_self(AlignSelf::Center)
+                            .entity_commands()
+                            .insert(StreakText::BackToBack);
+                    });
+
+                    column.row(|row| {
+                        row.style()
+                            .padding(UiRect::top(Val::Px(20.0)))
+                            .justify_content(JustifyContent::Center);
+                        row.label(LabelConfig::from("Time"))
+                            .style()
+                            .font_size(24.0)
+                            .align_self(AlignSelf::Center);
+                    });
+                    column.row(|row| {
+                        row.style().justify_content(JustifyContent::Center);
+                        row.label(LabelConfig::from(format_time(Duration::ZERO)))
+                            .style()
+                            .font_size(24.0)
+                            .align_self(AlignSelf::Center)
+                            .entity_commands()
+                            .insert(ClockText);
                     });
                 });
             },
         )
-        .insert(Name::new("NextPieceRoot"));
+        .insert(Name::new("HoldPieceRoot"));
 }
 
+#[allow(clippy::too_many_arguments)]
 fn update_stats(
     mut q_score: Query<(&mut Text, &ScoreText)>,
+    mode: Res<GameMode>,
     mut high_score: ResMut<HighScore>,
+    leaderboards: Res<Leaderboards>,
+    best_times: Res<BestTimes>,
     mut score: ResMut<Score>,
+    mut level: ResMut<Level>,
     mut score_event: EventReader<ScoreEvent>,
+    mut level_up_event: EventWriter<LevelUpEvent>,
     mut drop_timer: ResMut<MoveDownTimer>,
+    gravity: Res<GravityCurve>,
 ) {
     if score_event.is_empty() {
         return;
     }
 
+    let mut last_clear = None;
     score_event.read().for_each(|s| {
-        score.value += s.0.value;
-        score.lines += s.0.lines;
+        score.value += s.score.value;
+        score.lines += s.score.lines;
+        if s.score.lines > 0 || s.t_spin != TSpin::None {
+            let name = clear_name(s.score.lines, s.t_spin);
+            last_clear = Some(if s.back_to_back {
+                format!("B2B {name}")
+            } else {
+                name
+            });
+        }
     });
 
-    if score.value > high_score.0.value {
+    // The timed modes have their own leaderboard and the sprint is about the time
+    if mode.keeps_high_score() && score.value > high_score.0.value {
         high_score.0 = *score;
     }
+    // Each mode shows its own best result, and nothing when it doesn't keep one
+    let (best_title, best) = if mode.keeps_high_score() {
+        ("High Score", high_score.0.value.to_string())
+    } else if mode.has_leaderboard() {
+        let best = leaderboards.get(*mode).first().copied().unwrap_or_default();
+        ("High Score", best.max(score.value).to_string())
+    } else if mode.is_race() {
+        let best = best_times.get(*mode).map(format_time);
+        ("Best Time", best.unwrap_or_else(|| "-".to_string()))
+    } else {
+        ("", String::new())
+    };
 
-    // Decrease the speed of drop at every 500 score, making the drop faster up to 0.05s
-    // Using i32 div will make the score work as a step function
-    let level = score.value / 500;
-    let new_duration = Duration::from_secs_f32((1.0 - level as f32 * 0.1).max(0.05));
-    if drop_timer.0.duration() > new_duration {
+    let previous_level = level.value;
+    level.advance(score.lines);
+    // The clear that finishes the mode doesn't announce a level it won't be played
+    if level.value > previous_level && !mode.levels_complete(&level) {
+        level_up_event.send(LevelUpEvent(level.value));
+    }
+
+    // The pieces fall faster at every level, following the gravity curve
+    let new_duration = Duration::from_secs_f32(gravity.seconds_per_row(level.value));
+    if drop_timer.0.duration() != new_duration {
         drop_timer.0.set_duration(new_duration);
         drop_timer.0.reset();
     }
@@ -239,12 +583,145 @@ fn update_stats(
             ScoreText::Score => {
                 text.sections[0].value = score.value.to_string();
             }
+            ScoreText::BestTitle => {
+                text.sections[0].value = best_title.to_string();
+            }
             ScoreText::HighScore => {
-                text.sections[0].value = high_score.0.value.to_string();
+                text.sections[0].value.clone_from(&best);
             }
             ScoreText::Lines => {
                 text.sections[0].value = score.lines.to_string();
             }
+            ScoreText::Level => {
+                text.sections[0].value = level.value.to_string();
+            }
+            ScoreText::Clear => {
+                if let Some(name) = &last_clear {
+                    text.sections[0].value.clone_from(name);
+                }
+            }
+        }
+    }
+}
+
+/// The name shown for a clear, with the spin that made it
+fn clear_name(lines: u64, t_spin: TSpin) -> String {
+    let spin = match t_spin {
+        TSpin::None => "",
+        TSpin::Mini => "T-SPIN MINI ",
+        TSpin::Full => "T-SPIN ",
+    };
+    let lines = match lines {
+        0 => "",
+        1 => "SINGLE",
+        2 => "DOUBLE",
+        3 => "TRIPLE",
+        _ => "TETRIS",
+    };
+    format!("{spin}{lines}").trim_end().to_string()
+}
+
+/// Format a time as minutes, seconds and milliseconds, like `1:23.456`
+pub fn format_time(time: Duration) -> String {
+    let millis = time.as_millis();
+    format!(
+        "{}:{:02}.{:03}",
+        millis / 60_000,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+fn update_session(
+    time: Res<Time>,
+    mode: Res<GameMode>,
+    settings: Res<ModeSettings>,
+    mut session: ResMut<Session>,
+    mut locked_event: EventReader<PieceLockedEvent>,
+    mut q_clock: Query<&mut Text, With<ClockText>>,
+) {
+    session.time.tick(time.delta());
+    session.pieces += locked_event.read().count() as u64;
+    // The timed modes count down
+    let elapsed = session.time.elapsed();
+    let shown = match mode.time_limit(&settings) {
+        Some(limit) => limit.saturating_sub(elapsed),
+        None => elapsed,
+    };
+    for mut text in q_clock.iter_mut() {
+        text.sections[0].value = format_time(shown);
+    }
+}
+
+fn update_streak(mut q_streak: Query<(&mut Text, &StreakText)>, streak: Res<Streak>) {
+    for (mut text, streak_text) in q_streak.iter_mut() {
+        text.sections[0].value = match streak_text {
+            StreakText::Combo => streak.combo.unwrap_or_default().to_string(),
+            StreakText::BackToBack if streak.back_to_back => "BACK-TO-BACK".to_string(),
+            StreakText::BackToBack => String::new(),
+        };
+    }
+}
+
+fn show_perfect_clear(
+    mut commands: Commands,
+    mut perfect_clear_event: EventReader<PerfectClearEvent>,
+) {
+    let Some(perfect_clear) = perfect_clear_event.read().last() else {
+        return;
+    };
+    let lines = clear_name(perfect_clear.lines, TSpin::None);
+    spawn_callout(&mut commands, "AllClear", "ALL CLEAR", &lines, 40.0);
+}
+
+fn show_level_up(mut commands: Commands, mut level_up_event: EventReader<LevelUpEvent>) {
+    let Some(level_up) = level_up_event.read().last() else {
+        return;
+    };
+    let level = format!("LEVEL {}", level_up.0);
+    spawn_callout(&mut commands, "LevelUp", "LEVEL UP", &level, 15.0);
+}
+
+/// Helper function to show a message over the board for a while, starting at a percentage
+/// of the screen height so the callouts don't overlap
+fn spawn_callout(commands: &mut Commands, name: &str, title: &str, subtitle: &str, top: f32) {
+    commands
+        .ui_builder(UiRoot)
+        .column(|column| {
+            column
+                .label(LabelConfig::from(title))
+                .style()
+                .font_size(48.0)
+                .font_color(bevy::color::palettes::css::GOLD.into());
+            column
+                .label(LabelConfig::from(subtitle))
+                .style()
+                .font_size(32.0)
+                .font_color(bevy::color::palettes::css::GOLD.into());
+        })
+        .style()
+        .width(Val::Percent(100.0))
+        .height(Val::Percent(100.0))
+        .padding(UiRect::top(Val::Percent(top)))
+        .align_items(AlignItems::Center)
+        .justify_content(JustifyContent::Start)
+        .entity_commands()
+        .insert((
+            Callout(Timer::from_seconds(2.0, TimerMode::Once)),
+            StateScoped(GameState::Play),
+            Name::new(name.to_string()),
+        ));
+}
+
+/// Remove the callouts once their time is over
+fn update_callouts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Callout)>,
+) {
+    for (entity, mut callout) in query.iter_mut() {
+        if callout.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
         }
     }
 }
@@ -254,27 +731,94 @@ fn update_next_piece(
     q_camera: Query<(&Camera, &GlobalTransform), With<Camera>>,
     q_piece_label: Query<&GlobalTransform, With<NextPieceLabel>>,
     query: Query<Entity, With<NextPieceTag>>,
+    preview: Res<NextPreview>,
     mut next_piece_event: EventReader<NextPieceEvent>,
 ) {
     if next_piece_event.is_empty() {
         return;
     }
 
-    let (camera, camera_transform) = q_camera.single();
+    // Clear the previous next pieces
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
 
+    let upcoming = next_piece_event.read().last().unwrap().0.clone();
+    let camera = q_camera.single();
     let label_pos = q_piece_label.single();
 
-    // Clear the previous next piece
+    // The first piece is drawn at full size and the rest smaller, stacked under it
+    for (i, piece_type) in upcoming.into_iter().take(preview.0).enumerate() {
+        let (offset_y, scale) = if i == 0 {
+            (0.0, 1.0)
+        } else {
+            // Each smaller piece takes 2 scaled rows plus a small gap
+            let rows = 2.0 + (i - 1) as f32 * (2.0 * NEXT_PIECE_SCALE + 0.3);
+            (rows * BLOCK_SIZE, NEXT_PIECE_SCALE)
+        };
+        let parent = spawn_piece_preview(
+            &mut commands,
+            camera,
+            label_pos,
+            piece_type,
+            Color::from(&piece_type),
+            offset_y,
+            scale,
+        );
+        commands
+            .entity(parent)
+            .insert((NextPieceTag, Name::new(format!("NextPiece{}", i))));
+    }
+}
+
+fn update_hold_piece(
+    mut commands: Commands,
+    q_camera: Query<(&Camera, &GlobalTransform), With<Camera>>,
+    q_piece_label: Query<&GlobalTransform, With<HoldPieceLabel>>,
+    query: Query<Entity, With<HoldPieceTag>>,
+    hold: Res<HoldPiece>,
+) {
+    // Clear the previous held piece
     for entity in query.iter() {
         commands.entity(entity).despawn_recursive();
     }
 
-    let piece_type = next_piece_event.read().last().unwrap().0;
-    let piece_color = Color::from(&piece_type);
+    let Some(piece_type) = hold.piece else {
+        return;
+    };
+    // Grey out the piece while it can't be swapped
+    let piece_color = if hold.can_hold {
+        Color::from(&piece_type)
+    } else {
+        bevy::color::palettes::css::GRAY.into()
+    };
+    let parent = spawn_piece_preview(
+        &mut commands,
+        q_camera.single(),
+        q_piece_label.single(),
+        piece_type,
+        piece_color,
+        0.0,
+        1.0,
+    );
+    commands
+        .entity(parent)
+        .insert((HoldPieceTag, Name::new("HoldPiece")));
+}
 
+/// Helper function to draw a piece centered under one of the labels, returning the parent entity
+fn spawn_piece_preview(
+    commands: &mut Commands,
+    (camera, camera_transform): (&Camera, &GlobalTransform),
+    label_pos: &GlobalTransform,
+    piece_type: PieceType,
+    piece_color: Color,
+    offset_y: f32,
+    scale: f32,
+) -> Entity {
     let blocks = select_piece(piece_type);
 
-    // Calculate the first third of the screen width from the camera and place there the parent centered
+    // Calculate the position of the label in the world and place the parent under it
     let mut pos = camera
         .viewport_to_world_2d(
             camera_transform,
@@ -282,30 +826,35 @@ fn update_next_piece(
         )
         .unwrap();
 
-    pos.x -= BLOCK_SIZE * 3.0;
-    pos.y -= BLOCK_SIZE * 3.0;
+    pos.y -= BLOCK_SIZE * 2.0 + offset_y;
+
+    // Move the blocks so the center of the piece is at the parent
+    let (min, max) = blocks.iter().fold((Vec3::MAX, Vec3::MIN), |(min, max), b| {
+        (min.min(b.as_translation()), max.max(b.as_translation()))
+    });
+    let center = (min + max) / 2.0;
 
     let parent = commands
-        .spawn((
-            SpatialBundle {
-                transform: Transform::from_translation(pos.extend(0.0)),
-                ..Default::default()
-            },
-            NextPieceTag,
-            Name::new("NextPiece"),
-        ))
+        .spawn(SpatialBundle {
+            transform: Transform::from_translation(pos.extend(0.0)).with_scale(Vec3::splat(scale)),
+            ..Default::default()
+        })
         .id();
 
     blocks.iter().for_each(|b| {
+        let mut transform = b.as_transform();
+        transform.translation -= center;
         commands
             .spawn(SpriteBundle {
                 sprite: Sprite {
                     color: piece_color,
                     ..Default::default()
                 },
-                transform: b.as_transform(),
+                transform,
                 ..Default::default()
             })
             .set_parent(parent);
     });
+
+    parent
 }
@@ -1,7 +1,18 @@
-use bevy::prelude::*;
+use bevy::{
+    input::{
+        keyboard::{Key, KeyboardInput},
+        ButtonState,
+    },
+    prelude::*,
+};
 use sickle_ui::prelude::*;
 
-use crate::state::{AppState, GameState};
+use crate::{
+    mode::{GameMode, ModeSettings},
+    piece::{GameOverEvent, RandomizerKind, Seed},
+    state::{AppState, GameState},
+    stats::{format_time, BestTimes, Leaderboards, Score, Session},
+};
 
 pub struct TetrisUIPlugin;
 
@@ -10,26 +21,54 @@ impl Plugin for TetrisUIPlugin {
         app.add_systems(OnEnter(AppState::MainMenu), setup_main_menu)
             .add_systems(OnEnter(GameState::Pause), setup_pause_menu)
             .add_systems(OnEnter(GameState::GameOver), setup_gameover_menu)
+            .add_systems(OnEnter(GameState::Results), setup_results_menu)
             .add_systems(Update, handle_buttons)
             .add_systems(
                 Update,
-                handle_pause.run_if(
-                    in_state(AppState::GameState).and_then(not(in_state(GameState::GameOver))),
-                ),
+                (
+                    (edit_seed, update_seed_text.run_if(resource_changed::<Seed>)).chain(),
+                    update_randomizer_text.run_if(
+                        resource_changed::<RandomizerKind>
+                            .or_else(resource_changed::<ModeSettings>),
+                    ),
+                    update_mode_text.run_if(resource_changed::<GameMode>),
+                )
+                    .run_if(in_state(AppState::MainMenu)),
+            )
+            .add_systems(
+                Update,
+                handle_pause.run_if(in_state(GameState::Play).or_else(in_state(GameState::Pause))),
             );
     }
 }
 
+#[derive(Component)]
+struct SeedText;
+
+#[derive(Component)]
+struct RandomizerText;
+
+#[derive(Component)]
+struct ModeText;
+
 #[derive(Component)]
 enum MenuButton {
     Play,
+    Mode,
+    Randomizer,
     Continue,
     Restart,
     MainMenu,
     Quit,
 }
 
-pub fn setup_main_menu(mut commands: Commands) {
+pub fn setup_main_menu(
+    mut commands: Commands,
+    seed: Res<Seed>,
+    randomizer: Res<RandomizerKind>,
+    settings: Res<ModeSettings>,
+    mode: Res<GameMode>,
+) {
     commands
         .ui_builder(UiRoot)
         .column(|column| {
@@ -79,6 +118,50 @@ pub fn setup_main_menu(mut commands: Commands) {
                 })
                 .style()
                 .padding(UiRect::top(Val::Percent(15.0)));
+            column.row(|row| {
+                row.style()
+                    .align_items(AlignItems::Center)
+                    .justify_content(JustifyContent::Center);
+                row.spawn((
+                    MenuButton::Mode,
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(300.0),
+                            margin: UiRect::all(Val::Px(10.0)),
+                            justify_content: JustifyContent::Center,
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                ))
+                .label(LabelConfig::from(mode_text(&mode)))
+                .style()
+                .font_size(24.0)
+                .entity_commands()
+                .insert(ModeText);
+            });
+            column.row(|row| {
+                row.style()
+                    .align_items(AlignItems::Center)
+                    .justify_content(JustifyContent::Center);
+                row.spawn((
+                    MenuButton::Randomizer,
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(300.0),
+                            margin: UiRect::all(Val::Px(10.0)),
+                            justify_content: JustifyContent::Center,
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                ))
+                .label(LabelConfig::from(randomizer_text(&randomizer, &settings)))
+                .style()
+                .font_size(24.0)
+                .entity_commands()
+                .insert(RandomizerText);
+            });
             column.row(|row| {
                 row.style()
                     .align_items(AlignItems::Center)
@@ -99,6 +182,29 @@ pub fn setup_main_menu(mut commands: Commands) {
                 .style()
                 .font_size(32.0);
             });
+            column
+                .row(|row| {
+                    row.style()
+                        .align_items(AlignItems::Center)
+                        .justify_content(JustifyContent::Center);
+                    row.label(LabelConfig::from(seed_text(&seed)))
+                        .style()
+                        .font_size(24.0)
+                        .entity_commands()
+                        .insert(SeedText);
+                })
+                .style()
+                .padding(UiRect::top(Val::Px(20.0)));
+            column.row(|row| {
+                row.style()
+                    .align_items(AlignItems::Center)
+                    .justify_content(JustifyContent::Center);
+                row.label(LabelConfig::from(
+                    "type a number to choose, backspace to erase",
+                ))
+                .style()
+                .font_size(16.0);
+            });
         })
         .style()
         .width(Val::Percent(100.0))
@@ -175,7 +281,31 @@ pub fn setup_pause_menu(mut commands: Commands) {
         .insert((StateScoped(GameState::Pause), Name::new("PauseMenu")));
 }
 
-pub fn setup_gameover_menu(mut commands: Commands) {
+pub fn setup_gameover_menu(
+    mut commands: Commands,
+    seed: Res<Seed>,
+    mode: Res<GameMode>,
+    score: Res<Score>,
+    session: Res<Session>,
+    mut game_over_event: EventReader<GameOverEvent>,
+) {
+    let reason = game_over_event
+        .read()
+        .last()
+        .map(|event| event.0.name())
+        .unwrap_or_default();
+    let mut results = Vec::new();
+    // Surviving the rising garbage is the goal, so the time is the result
+    if mode.has_rising_garbage() {
+        results.push(format!("Survived: {}", format_time(session.time.elapsed())));
+    }
+    // The score of a timed mode still counts when it ends early
+    if mode.has_leaderboard() {
+        results.push(format!("Score: {}", score.value));
+        if let Some(rank) = session.rank {
+            results.push(format!("Leaderboard: #{}", rank + 1));
+        }
+    }
     commands
         .ui_builder(UiRoot)
         .column(|column| {
@@ -187,6 +317,28 @@ pub fn setup_gameover_menu(mut commands: Commands) {
                     .style()
                     .font_size(60.0);
             });
+            column.row(|row| {
+                row.style()
+                    .align_items(AlignItems::Center)
+                    .justify_content(JustifyContent::Center);
+                row.label(LabelConfig::from(reason)).style().font_size(32.0);
+            });
+            for line in results {
+                column.row(|row| {
+                    row.style()
+                        .align_items(AlignItems::Center)
+                        .justify_content(JustifyContent::Center);
+                    row.label(LabelConfig::from(line)).style().font_size(32.0);
+                });
+            }
+            column.row(|row| {
+                row.style()
+                    .align_items(AlignItems::Center)
+                    .justify_content(JustifyContent::Center);
+                row.label(LabelConfig::from(format!("Seed: {}", seed.current)))
+                    .style()
+                    .font_size(24.0);
+            });
 
             column
                 .row(|row| {
@@ -241,10 +393,125 @@ pub fn setup_gameover_menu(mut commands: Commands) {
         .insert((StateScoped(GameState::GameOver), Name::new("GameOverMenu")));
 }
 
+pub fn setup_results_menu(
+    mut commands: Commands,
+    seed: Res<Seed>,
+    mode: Res<GameMode>,
+    score: Res<Score>,
+    session: Res<Session>,
+    best_times: Res<BestTimes>,
+    leaderboards: Res<Leaderboards>,
+) {
+    let time = session.time.elapsed();
+    let (title, mut lines) = if mode.has_leaderboard() {
+        (
+            "TIME'S UP".to_string(),
+            vec![format!("Score: {}", score.value)],
+        )
+    } else {
+        (
+            format!("{} COMPLETE", mode.name()),
+            vec![
+                format!("Score: {}", score.value),
+                format!("Time: {}", format_time(time)),
+            ],
+        )
+    };
+    lines.push(format!("Pieces: {}", session.pieces));
+    lines.push(format!("PPS: {:.2}", session.pps()));
+    // The same seed gives the same pieces, so a race can be played again
+    lines.push(format!("Seed: {}", seed.current));
+    if mode.has_leaderboard() {
+        // Mark the score of this game, if it made it to the leaderboard
+        for (rank, best) in leaderboards.get(*mode).iter().enumerate() {
+            let marker = if Some(rank) == session.rank { " <" } else { "" };
+            lines.push(format!("{}. {}{}", rank + 1, best, marker));
+        }
+    } else {
+        let best = best_times.get(*mode).unwrap_or(time);
+        lines.push(if time <= best {
+            "NEW BEST TIME!".to_string()
+        } else {
+            format!("Best: {}", format_time(best))
+        });
+    }
+    commands
+        .ui_builder(UiRoot)
+        .column(|column| {
+            column.row(|row| {
+                row.style()
+                    .align_items(AlignItems::Center)
+                    .justify_content(JustifyContent::Center);
+                row.label(LabelConfig::from(title)).style().font_size(60.0);
+            });
+            for line in lines {
+                column.row(|row| {
+                    row.style()
+                        .align_items(AlignItems::Center)
+                        .justify_content(JustifyContent::Center);
+                    row.label(LabelConfig::from(line)).style().font_size(32.0);
+                });
+            }
+
+            column
+                .row(|row| {
+                    row.style()
+                        .align_items(AlignItems::Center)
+                        .justify_content(JustifyContent::Center);
+                    row.spawn((
+                        MenuButton::Restart,
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(200.0),
+                                margin: UiRect::all(Val::Px(10.0)),
+                                justify_content: JustifyContent::Center,
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                    ))
+                    .label(LabelConfig::from("RESTART"))
+                    .style()
+                    .font_size(32.0);
+                })
+                .style()
+                .padding(UiRect::top(Val::Percent(10.0)));
+            column.row(|row| {
+                row.style()
+                    .align_items(AlignItems::Center)
+                    .justify_content(JustifyContent::Center);
+                row.spawn((
+                    MenuButton::MainMenu,
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(200.0),
+                            margin: UiRect::all(Val::Px(10.0)),
+                            justify_content: JustifyContent::Center,
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                ))
+                .label(LabelConfig::from("MAIN MENU"))
+                .style()
+                .font_size(32.0);
+            });
+        })
+        .style()
+        .width(Val::Percent(100.0))
+        .height(Val::Percent(100.0))
+        .align_items(AlignItems::Center)
+        .justify_content(JustifyContent::Center)
+        .entity_commands()
+        .insert((StateScoped(GameState::Results), Name::new("ResultsMenu")));
+}
+
 fn handle_buttons(
-    mut query: Query<(&Interaction, &MenuButton, &mut BackgroundColor)>,
+    mut query: Query<(&Interaction, &MenuButton, &mut BackgroundColor), Changed<Interaction>>,
     mut state: ResMut<NextState<AppState>>,
     mut game_state: ResMut<NextState<GameState>>,
+    mut settings: ResMut<ModeSettings>,
+    mut mode: ResMut<GameMode>,
 ) {
     for (interaction, button, mut background) in query.iter_mut() {
         if *interaction == Interaction::Pressed {
@@ -253,6 +520,14 @@ fn handle_buttons(
                     // Change the state to the game
                     state.set(AppState::GameState);
                 }
+                MenuButton::Mode => {
+                    // Cycle through the modes at each click
+                    *mode = mode.cycle();
+                }
+                MenuButton::Randomizer => {
+                    // Cycle through the randomizers at each click, then back to the one of the mode
+                    settings.cycle_randomizer();
+                }
                 MenuButton::Quit => {
                     // Just close the game
                     std::process::exit(0);
@@ -291,3 +566,77 @@ fn handle_pause(
         }
     }
 }
+
+fn seed_text(seed: &Seed) -> String {
+    match seed.fixed {
+        Some(value) => format!("Seed: {}", value),
+        None => "Seed: RANDOM".to_string(),
+    }
+}
+
+/// Let the player type the seed of the pieces in the main menu
+fn edit_seed(mut keyboard_events: EventReader<KeyboardInput>, mut seed: ResMut<Seed>) {
+    for event in keyboard_events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+        match &event.logical_key {
+            Key::Character(character) => {
+                let Some(digit) = character.chars().next().and_then(|c| c.to_digit(10)) else {
+                    continue;
+                };
+                // Ignore the digit if the seed doesn't fit anymore
+                if let Some(value) = seed
+                    .fixed
+                    .unwrap_or_default()
+                    .checked_mul(10)
+                    .and_then(|value| value.checked_add(digit as u64))
+                {
+                    seed.fixed = Some(value);
+                }
+            }
+            Key::Backspace => {
+                // Erasing the last digit goes back to a random seed
+                seed.fixed = seed
+                    .fixed
+                    .map(|value| value / 10)
+                    .filter(|&value| value > 0);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn mode_text(mode: &GameMode) -> String {
+    format!("MODE: {}", mode.name())
+}
+
+fn update_mode_text(mut query: Query<&mut Text, With<ModeText>>, mode: Res<GameMode>) {
+    for mut text in query.iter_mut() {
+        text.sections[0].value = mode_text(&mode);
+    }
+}
+
+fn randomizer_text(randomizer: &RandomizerKind, settings: &ModeSettings) -> String {
+    if settings.randomizer.is_some() {
+        format!("PIECES: {}", randomizer.name())
+    } else {
+        format!("PIECES: {} (MODE)", randomizer.name())
+    }
+}
+
+fn update_randomizer_text(
+    mut query: Query<&mut Text, With<RandomizerText>>,
+    randomizer: Res<RandomizerKind>,
+    settings: Res<ModeSettings>,
+) {
+    for mut text in query.iter_mut() {
+        text.sections[0].value = randomizer_text(&randomizer, &settings);
+    }
+}
+
+fn update_seed_text(mut query: Query<&mut Text, With<SeedText>>, seed: Res<Seed>) {
+    for mut text in query.iter_mut() {
+        text.sections[0].value = seed_text(&seed);
+    }
+}